@@ -0,0 +1,51 @@
+use std::{fs, io};
+
+// cpus the process is allowed to run on.
+#[cfg(target_os = "linux")]
+pub fn allowed_cpus() -> Vec<usize> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    match unsafe { libc::sched_getaffinity(0, size, &mut set) } {
+        0 => (0..libc::CPU_SETSIZE as usize)
+            .filter(|cpu| unsafe { libc::CPU_ISSET(*cpu, &set) })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn allowed_cpus() -> Vec<usize> {
+    vec![]
+}
+
+// pin the calling thread to `cpu`.
+#[cfg(target_os = "linux")]
+pub fn pin(cpu: usize) -> io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    unsafe { libc::CPU_SET(cpu, &mut set) };
+    match unsafe { libc::sched_setaffinity(0, size, &set) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin(_cpu: usize) -> io::Result<()> {
+    Ok(())
+}
+
+// number of numa nodes, 1 on non-numa systems.
+pub fn numa_nodes() -> usize {
+    let nodes = fs::read_dir("/sys/devices/system/node").map(|entries| {
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                name.starts_with("node") && name[4..].parse::<usize>().is_ok()
+            })
+            .count()
+    });
+    nodes.unwrap_or(0).max(1)
+}
@@ -0,0 +1,53 @@
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+
+// start address of io buffers is aligned to BLOCK_ALIGN, page size, which
+// satisfies O_DIRECT on all common devices.
+const BLOCK_ALIGN: usize = 4096;
+
+// Fixed size io buffer, aligned in memory so that it can be handed to
+// a file opened with O_DIRECT.
+pub struct Block {
+    buf: Vec<u8>,
+    off: usize,
+    len: usize,
+}
+
+impl Block {
+    pub fn new(len: usize, fill: u8) -> Block {
+        let buf = vec![fill; len + BLOCK_ALIGN];
+        let off = (BLOCK_ALIGN - (buf.as_ptr() as usize % BLOCK_ALIGN)) % BLOCK_ALIGN;
+        Block { buf, off, len }
+    }
+
+    // touch every page of the buffer, so that the first io on it doesn't
+    // take minor page faults, a zero filled buffer is only mapped lazily.
+    pub fn prefault(&mut self) {
+        for off in (0..self.buf.len()).step_by(BLOCK_ALIGN) {
+            let byte: *mut u8 = &mut self.buf[off];
+            unsafe { ptr::write_volatile(byte, ptr::read_volatile(byte)) };
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf[self.off..self.off + self.len]
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.buf[self.off..self.off + self.len]
+    }
+}
+
+impl Deref for Block {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl DerefMut for Block {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
@@ -0,0 +1,93 @@
+use std::time;
+
+use structopt::StructOpt;
+
+use crate::{do_append, stats, Context, Opt, SyncMode, MIN_SYNC_LATENCY};
+
+// preset workload, each entry is (block-size, sync) run for CALIBRATE_SECS.
+const CALIBRATE_RUNS: [(&str, bool); 4] =
+    [("4K", true), ("4K", false), ("1M", true), ("1M", false)];
+const CALIBRATE_SECS: &str = "3";
+
+struct Measure {
+    block_size: String,
+    sync: bool,
+    p50: u64,
+    p99: u64,
+    throughput: f64, // MB/sec
+}
+
+// run a fixed small workload on `path` and classify the device backing it.
+pub fn run(path: &str) {
+    println!("calibrating `{}` ..", path);
+
+    let mut measures = vec![];
+    for (block_size, sync) in CALIBRATE_RUNS.iter() {
+        let args = vec![
+            "diskio",
+            "--appenders",
+            "1",
+            "--duration",
+            CALIBRATE_SECS,
+            "--block-size",
+            block_size,
+            path,
+        ];
+        let opt = Opt::from_iter(args);
+        let bsize = opt.clone().block_size.get_blocks()[0];
+
+        let start = time::Instant::now();
+        let mode = if *sync { SyncMode::All } else { SyncMode::None };
+        let mut ss = do_append(bsize, &opt, mode);
+        let elapsed = start.elapsed().as_secs_f64();
+        Context::drop_data_file(0, &opt);
+
+        ss.sync_latencies.sort();
+        measures.push(Measure {
+            block_size: block_size.to_string(),
+            sync: *sync,
+            p50: stats::percentile(&ss.sync_latencies, 0.50),
+            p99: stats::percentile(&ss.sync_latencies, 0.99),
+            throughput: (ss.bytes as f64) / (1024_f64 * 1024_f64) / elapsed,
+        });
+    }
+
+    println!();
+    println!("calibration report for `{}`", path);
+    for m in measures.iter() {
+        println!(
+            "  {:>3} {:<7} p50 {:>8}us  p99 {:>8}us  {:>10.2} MB/sec",
+            m.block_size,
+            if m.sync { "sync" } else { "nosync" },
+            m.p50,
+            m.p99,
+            m.throughput
+        );
+    }
+    println!("  device looks like: {}", classify(&measures));
+}
+
+// Rough ranges, rotational disks take milliseconds to sync a small block,
+// SATA SSDs take hundreds of microseconds and are capped by the ~600MB/s
+// link, NVMe devices sync faster and stream beyond the SATA link.
+fn classify(measures: &[Measure]) -> &'static str {
+    let find = |bsize: &str, sync: bool| {
+        measures
+            .iter()
+            .find(|m| m.block_size == bsize && m.sync == sync)
+    };
+    let (small, large) = match (find("4K", true), find("1M", true)) {
+        (Some(small), Some(large)) => (small, large),
+        _ => return "unknown",
+    };
+
+    if small.p50 < MIN_SYNC_LATENCY {
+        "non-durable cache (tmpfs, write-back cache or volatile write cache)"
+    } else if small.p50 >= 2000 {
+        "HDD (rotational)"
+    } else if large.throughput > 600_f64 || small.p50 < 100 {
+        "NVMe SSD"
+    } else {
+        "SATA SSD"
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    fs, io,
+    sync::{Arc, Condvar, Mutex},
+    thread, time,
+};
+
+// Group commit, writers only write and a single committer thread syncs
+// all the files every `interval`. A writer waits for the first commit
+// that starts after its write, that is when its data is durable.
+pub struct Committer {
+    files: Vec<fs::File>,
+    interval: time::Duration,
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    started: u64, // commits started
+    done: u64,    // commits completed
+    stop: bool,
+    commits: u64,
+    latencies: Vec<u64>, // commit latency, in microseconds
+}
+
+impl Committer {
+    pub fn new(files: Vec<fs::File>, interval: time::Duration) -> Committer {
+        Committer {
+            files,
+            interval,
+            state: Mutex::new(State::default()),
+            cond: Condvar::new(),
+        }
+    }
+
+    // run the committer in its own thread, until stop() is called.
+    pub fn spawn(self: &Arc<Committer>) -> thread::JoinHandle<io::Result<()>> {
+        let committer = Arc::clone(self);
+        thread::spawn(move || committer.run())
+    }
+
+    fn run(&self) -> io::Result<()> {
+        let res = self.commit_loop();
+        // on failure, don't leave writers waiting for a commit.
+        self.stop();
+        res
+    }
+
+    fn commit_loop(&self) -> io::Result<()> {
+        loop {
+            thread::sleep(self.interval);
+            let seqno = {
+                let mut state = self.state.lock().unwrap();
+                if state.stop {
+                    break Ok(());
+                }
+                state.started += 1;
+                state.started
+            };
+            let start = time::Instant::now();
+            for file in self.files.iter() {
+                file.sync_all()?;
+            }
+            let mut state = self.state.lock().unwrap();
+            state.done = seqno;
+            state.commits += 1;
+            state.latencies.push(start.elapsed().as_micros() as u64);
+            self.cond.notify_all();
+        }
+    }
+
+    // block until a commit, started after this call, completes.
+    pub fn wait(&self) {
+        let mut state = self.state.lock().unwrap();
+        let seqno = state.started + 1;
+        while state.done < seqno && !state.stop {
+            state = self.cond.wait(state).unwrap();
+        }
+    }
+
+    // stop the committer, release writers still waiting for a commit.
+    pub fn stop(&self) {
+        self.state.lock().unwrap().stop = true;
+        self.cond.notify_all();
+    }
+
+    // number of commits and their latencies.
+    pub fn to_commits(&self) -> (u64, Vec<u64>) {
+        let state = self.state.lock().unwrap();
+        (state.commits, state.latencies.clone())
+    }
+}
@@ -0,0 +1,199 @@
+use std::{fs, path};
+
+use structopt::{clap::ErrorKind, StructOpt};
+
+use crate::{error::DiskioError, Opt};
+
+// Workload definition loaded from a TOML file, or from a JSON file when
+// its name ends with `.json`. Top level keys are long names of command
+// line options, with `_` or `-` as separator, and values are a string,
+// number, boolean or an array of them. Arrays are joined with `,`, which
+// is the list form accepted by size arguments. `path` is the directory
+// to measure. A `true` boolean passes the flag, `false` leaves it out.
+// Nested tables are not supported, unknown keys are rejected, values
+// are checked by the command line parser, like:
+//
+//     path = "/mnt/ssd"
+//     block_size = ["4K", "64K", "1M"]
+//     appenders = 2
+//     plot = true
+#[derive(Debug)]
+pub struct Config {
+    pub path: Option<String>,
+    pub args: Vec<String>,
+}
+
+// value of a key, flattened to what the command line takes.
+enum Value {
+    Flag(bool),
+    Arg(String),
+}
+
+impl Config {
+    pub fn load(file: &str) -> Result<Config, DiskioError> {
+        let text = fs::read_to_string(file)?;
+        let json = path::Path::new(file)
+            .extension()
+            .is_some_and(|ext| ext == "json");
+        Config::parse(&text, json).map_err(|msg| DiskioError::Config(format!("{}: {}", file, msg)))
+    }
+
+    fn parse(text: &str, json: bool) -> Result<Config, String> {
+        let items = match json {
+            true => from_json(text)?,
+            false => from_toml(text)?,
+        };
+        let mut config = Config {
+            path: None,
+            args: vec![],
+        };
+        for (key, value) in items.into_iter() {
+            let key = key.replace('_', "-");
+            match (key.as_str(), value) {
+                ("path", Value::Arg(path)) => config.path = Some(path),
+                ("path", Value::Flag(_)) => return Err("`path` must be a string".to_string()),
+                (key, _) if !known(key) => return Err(format!("unknown option `{}`", key)),
+                (key, Value::Flag(true)) => config.args.push(format!("--{}", key)),
+                (_, Value::Flag(false)) => (),
+                (key, Value::Arg(value)) => {
+                    config.args.push(format!("--{}", key));
+                    config.args.push(value);
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+// whether `key` is the long name of a command line option.
+fn known(key: &str) -> bool {
+    let arg = format!("--{}", key);
+    match Opt::clap().get_matches_from_safe(vec!["diskio", arg.as_str()]) {
+        Err(err) => err.kind != ErrorKind::UnknownArgument,
+        Ok(_) => true,
+    }
+}
+
+fn from_toml(text: &str) -> Result<Vec<(String, Value)>, String> {
+    let table = match text.parse::<toml::Value>() {
+        Ok(toml::Value::Table(table)) => table,
+        Ok(_) => return Err("expected a table of options".to_string()),
+        Err(err) => return Err(err.to_string()),
+    };
+    let scalar = |key: &str, value: &toml::Value| match value {
+        toml::Value::String(s) => Ok(s.clone()),
+        toml::Value::Integer(n) => Ok(n.to_string()),
+        toml::Value::Float(n) => Ok(n.to_string()),
+        _ => Err(format!("unsupported value for `{}`", key)),
+    };
+    let mut items = vec![];
+    for (key, value) in table.iter() {
+        let value = match value {
+            toml::Value::Boolean(b) => Value::Flag(*b),
+            toml::Value::Array(values) => {
+                let values: Result<Vec<String>, String> =
+                    values.iter().map(|v| scalar(key, v)).collect();
+                Value::Arg(values?.join(","))
+            }
+            value => Value::Arg(scalar(key, value)?),
+        };
+        items.push((key.clone(), value));
+    }
+    Ok(items)
+}
+
+fn from_json(text: &str) -> Result<Vec<(String, Value)>, String> {
+    let object = match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(serde_json::Value::Object(object)) => object,
+        Ok(_) => return Err("expected an object of options".to_string()),
+        Err(err) => return Err(err.to_string()),
+    };
+    let scalar = |key: &str, value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => Ok(s.clone()),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        _ => Err(format!("unsupported value for `{}`", key)),
+    };
+    let mut items = vec![];
+    for (key, value) in object.iter() {
+        let value = match value {
+            serde_json::Value::Bool(b) => Value::Flag(*b),
+            serde_json::Value::Array(values) => {
+                let values: Result<Vec<String>, String> =
+                    values.iter().map(|v| scalar(key, v)).collect();
+                Value::Arg(values?.join(","))
+            }
+            value => Value::Arg(scalar(key, value)?),
+        };
+        items.push((key.clone(), value));
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toml(text: &str) -> Result<Config, String> {
+        Config::parse(text, false)
+    }
+
+    #[test]
+    fn test_toml() {
+        let config = toml(
+            "# a sweep\n\
+             path = \"/mnt/ssd\"\n\
+             block_size = [\"4K\", \"64K\", \"1M\"] # ladder\n\
+             appenders = 2\n\
+             plot = true\n\
+             no_plot = false\n",
+        )
+        .unwrap();
+        assert_eq!(config.path.as_deref(), Some("/mnt/ssd"));
+        let want = vec!["--appenders", "2", "--block-size", "4K,64K,1M", "--plot"];
+        assert_eq!(config.args, want);
+    }
+
+    #[test]
+    fn test_toml_quoting() {
+        let config = toml("label = \"run #1, \\\"cold\\\"\"\npath = 'C:\\tmp'\n").unwrap();
+        assert_eq!(config.args, vec!["--label", "run #1, \"cold\""]);
+        assert_eq!(config.path.as_deref(), Some("C:\\tmp"));
+    }
+
+    #[test]
+    fn test_toml_rejected() {
+        let rejected = [
+            "no_such_option = 1",
+            "appenders = ",
+            "appenders = 2\nappenders = 3",
+            "[workload]\nappenders = 2",
+            "block_size = [[\"4K\"]]",
+            "path = true",
+            "label = \"unterminated",
+        ];
+        for text in rejected.iter() {
+            assert!(toml(text).is_err(), "{:?} accepted", text);
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        let text =
+            r#"{"path": "/mnt/ssd", "block-size": ["4K", "1M"], "writers": 4, "plot": true}"#;
+        let config = Config::parse(text, true).unwrap();
+        assert_eq!(config.path.as_deref(), Some("/mnt/ssd"));
+        // keys come in sorted order.
+        let want = vec!["--block-size", "4K,1M", "--plot", "--writers", "4"];
+        assert_eq!(config.args, want);
+
+        let rejected = [
+            r#"{"no-such-option": 1}"#,
+            r#"["4K"]"#,
+            r#"{"label": null}"#,
+            "{",
+        ];
+        for text in rejected.iter() {
+            assert!(Config::parse(text, true).is_err(), "{:?} accepted", text);
+        }
+    }
+}
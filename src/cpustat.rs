@@ -0,0 +1,120 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread, time,
+};
+
+// Snapshot of the aggregate `cpu` line of /proc/stat, in clock ticks.
+#[derive(Clone, Debug, Default)]
+pub struct CpuTimes {
+    user: u64, // including nice
+    system: u64,
+    idle: u64,
+    iowait: u64,
+    other: u64, // irq, softirq and steal
+}
+
+impl CpuTimes {
+    // None when /proc/stat is not available.
+    #[cfg(target_os = "linux")]
+    pub fn sample() -> Option<CpuTimes> {
+        let text = std::fs::read_to_string("/proc/stat").ok()?;
+        let line = text.lines().find(|l| l.starts_with("cpu "))?;
+        let fields: Vec<u64> = line
+            .split_whitespace()
+            .skip(1)
+            .filter_map(|f| f.parse::<u64>().ok())
+            .collect();
+        let field = |off: usize| fields.get(off).cloned().unwrap_or(0);
+        Some(CpuTimes {
+            user: field(0) + field(1),
+            system: field(2),
+            idle: field(3),
+            iowait: field(4),
+            other: field(5) + field(6) + field(7),
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn sample() -> Option<CpuTimes> {
+        None
+    }
+
+    fn total(&self) -> u64 {
+        self.user + self.system + self.idle + self.iowait + self.other
+    }
+}
+
+// Share of cpu time, in percent, spent in each state over an interval,
+// across all cpus.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CpuUsage {
+    pub user: f64,
+    pub system: f64,
+    pub iowait: f64,
+}
+
+impl CpuUsage {
+    // None when no tick elapsed between the snapshots.
+    pub fn between(earlier: &CpuTimes, later: &CpuTimes) -> Option<CpuUsage> {
+        let total = later.total().saturating_sub(earlier.total());
+        if total == 0 {
+            return None;
+        }
+        let pct = |a: u64, b: u64| (a.saturating_sub(b) as f64) * 100_f64 / (total as f64);
+        Some(CpuUsage {
+            user: pct(later.user, earlier.user),
+            system: pct(later.system, earlier.system),
+            iowait: pct(later.iowait, earlier.iowait),
+        })
+    }
+}
+
+// Background thread sampling cpu usage every interval, to report both
+// the average over a phase and its peaks.
+pub struct CpuSampler {
+    stop: Arc<AtomicBool>,
+    handle: thread::JoinHandle<(Option<CpuUsage>, Vec<CpuUsage>)>,
+}
+
+impl CpuSampler {
+    pub fn start(interval: time::Duration) -> CpuSampler {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let mut usages = vec![];
+                let first = CpuTimes::sample();
+                let mut prev = first.clone();
+                while !stop.load(Ordering::Relaxed) {
+                    thread::park_timeout(interval);
+                    let next = CpuTimes::sample();
+                    if stop.load(Ordering::Relaxed) {
+                        prev = next;
+                        break;
+                    }
+                    if let (Some(t1), Some(t2)) = (&prev, &next) {
+                        usages.extend(CpuUsage::between(t1, t2));
+                    }
+                    prev = next;
+                }
+                let avg = match (&first, &prev) {
+                    (Some(t1), Some(t2)) => CpuUsage::between(t1, t2),
+                    _ => None,
+                };
+                (avg, usages)
+            })
+        };
+        CpuSampler { stop, handle }
+    }
+
+    // stop sampling and return the average over the whole run, and the
+    // per-interval samples.
+    pub fn finish(self) -> (Option<CpuUsage>, Vec<CpuUsage>) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.thread().unpark();
+        self.handle.join().unwrap_or_default()
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread, time,
+};
+
+// Snapshot of /proc/diskstats counters for the device backing a path.
+#[derive(Clone, Debug)]
+pub struct DiskStats {
+    pub name: String,
+    pub io_ticks: u64,        // milliseconds spent doing I/O
+    pub sectors_written: u64, // always in 512 byte units
+    pub flushes: Option<u64>, // available only from kernel 5.5
+}
+
+impl DiskStats {
+    // None when diskstats is not available, or when the device is not
+    // listed in it, like tmpfs and other virtual filesystems.
+    #[cfg(target_os = "linux")]
+    pub fn sample<P: AsRef<path::Path>>(path: P) -> Option<DiskStats> {
+        use std::{fs, os::unix::fs::MetadataExt};
+
+        // path might not exist yet, fall back to its nearest ancestor.
+        let dev = path
+            .as_ref()
+            .ancestors()
+            .find_map(|p| fs::metadata(p).ok())?
+            .dev();
+        let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+        let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+
+        let text = fs::read_to_string("/proc/diskstats").ok()?;
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 3 {
+                continue;
+            }
+            let (mj, mn) = (fields[0].parse::<u64>(), fields[1].parse::<u64>());
+            if mj != Ok(major) || mn != Ok(minor) {
+                continue;
+            }
+            let field = |off: usize| fields.get(off).and_then(|f| f.parse::<u64>().ok());
+            return Some(DiskStats {
+                name: fields[2].to_string(),
+                io_ticks: field(12)?,
+                sectors_written: field(9)?,
+                flushes: field(18),
+            });
+        }
+        None
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn sample<P: AsRef<path::Path>>(_path: P) -> Option<DiskStats> {
+        None
+    }
+
+    // bytes written to the device, by everyone, since `earlier`.
+    pub fn written_since(&self, earlier: &DiskStats) -> u64 {
+        self.sectors_written.saturating_sub(earlier.sectors_written) * 512
+    }
+
+    pub fn flushes_since(&self, earlier: &DiskStats) -> Option<u64> {
+        Some(self.flushes?.saturating_sub(earlier.flushes?))
+    }
+}
+
+// Background thread sampling device utilization, as percentage of wall
+// clock time the device was busy doing I/O, like iostat's %util.
+pub struct UtilSampler {
+    stop: Arc<AtomicBool>,
+    handle: thread::JoinHandle<Vec<f64>>,
+}
+
+impl UtilSampler {
+    pub fn start(path: String, interval: time::Duration) -> UtilSampler {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let mut utils = vec![];
+                let mut prev = (time::Instant::now(), DiskStats::sample(&path));
+                while !stop.load(Ordering::Relaxed) {
+                    thread::park_timeout(interval);
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let next = (time::Instant::now(), DiskStats::sample(&path));
+                    if let ((t1, Some(s1)), (t2, Some(s2))) = (&prev, &next) {
+                        let busy = s2.io_ticks.saturating_sub(s1.io_ticks) as f64;
+                        let wall = t2.duration_since(*t1).as_millis().max(1) as f64;
+                        utils.push((busy * 100_f64 / wall).min(100_f64));
+                    }
+                    prev = next;
+                }
+                utils
+            })
+        };
+        UtilSampler { stop, handle }
+    }
+
+    // stop sampling and return the utilization samples.
+    pub fn finish(self) -> Vec<f64> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.thread().unpark();
+        self.handle.join().unwrap_or_default()
+    }
+}
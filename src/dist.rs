@@ -0,0 +1,173 @@
+use std::str::FromStr;
+
+use rand::Rng;
+
+use crate::SizeArg;
+
+// Distribution of sizes, either a weighted list of sizes, like
+// `4K:70,64K:25,1M:5`, or a bounded power-law, like `power:1K..16M:1.2`,
+// where most samples are close to the lower bound and a few are large.
+// The power-law exponent is optional and defaults to 1.0.
+#[derive(Debug, Clone)]
+pub enum SizeDist {
+    Weighted(Vec<(isize, u32)>),
+    PowerLaw { min: isize, max: isize, alpha: f64 },
+}
+
+impl FromStr for SizeDist {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SizeDist, Self::Err> {
+        if let Some(spec) = s.strip_prefix("power:") {
+            let mut parts = spec.splitn(2, ':');
+            let range = parts.next().unwrap_or("");
+            let alpha = match parts.next() {
+                Some(alpha) => alpha.parse::<f64>().map_err(|e| format!("{}", e))?,
+                None => 1.0,
+            };
+            let (min, max) = match range.find("..") {
+                Some(off) => (&range[..off], &range[off + 2..]),
+                None => return Err(format!("invalid power-law range `{}`", range)),
+            };
+            let (min, max) = (SizeArg::to_isize(min)?, SizeArg::to_isize(max)?);
+            if min <= 0 || max < min || alpha <= 0.0 {
+                return Err(format!("invalid power-law `{}`", s));
+            }
+            return Ok(SizeDist::PowerLaw { min, max, alpha });
+        }
+
+        let mut items = vec![];
+        for item in s.split(',') {
+            let (size, weight) = match item.find(':') {
+                Some(off) => (&item[..off], &item[off + 1..]),
+                None => return Err(format!("expected `size:weight`, found `{}`", item)),
+            };
+            let weight = weight.parse::<u32>().map_err(|e| format!("{}", e))?;
+            match SizeArg::to_isize(size)? {
+                size if size <= 0 => return Err(format!("invalid size in `{}`", item)),
+                size => items.push((size, weight)),
+            }
+        }
+        match items.iter().map(|(_, w)| w).sum::<u32>() {
+            0 => Err(format!("weights in `{}` add up to zero", s)),
+            _ => Ok(SizeDist::Weighted(items)),
+        }
+    }
+}
+
+impl SizeDist {
+    pub fn max(&self) -> isize {
+        match self {
+            SizeDist::Weighted(items) => items.iter().map(|(s, _)| *s).max().unwrap_or(0),
+            SizeDist::PowerLaw { max, .. } => *max,
+        }
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> isize {
+        match self {
+            SizeDist::Weighted(items) => {
+                let total: u32 = items.iter().map(|(_, w)| w).sum();
+                let mut pick = rng.gen_range(0, total);
+                for (size, weight) in items.iter() {
+                    if pick < *weight {
+                        return *size;
+                    }
+                    pick -= weight;
+                }
+                unreachable!()
+            }
+            SizeDist::PowerLaw { min, max, alpha } => {
+                // inverse transform of bounded pareto distribution.
+                let (l, h) = (*min as f64, *max as f64);
+                let u: f64 = rng.gen_range(0.0, 1.0);
+                let x = l / (1.0 - u * (1.0 - (l / h).powf(*alpha))).powf(1.0 / alpha);
+                (x as isize).max(*min).min(*max)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    #[test]
+    fn test_parse_weighted() {
+        match "4K:70,64K:25,1M:5".parse::<SizeDist>().unwrap() {
+            SizeDist::Weighted(items) => {
+                assert_eq!(items, vec![(4096, 70), (65536, 25), (1048576, 5)])
+            }
+            dist => panic!("{:?}", dist),
+        }
+    }
+
+    #[test]
+    fn test_parse_power() {
+        match "power:1K..16M:1.2".parse::<SizeDist>().unwrap() {
+            SizeDist::PowerLaw { min, max, alpha } => {
+                assert_eq!((min, max, alpha), (1024, 16 * 1024 * 1024, 1.2));
+            }
+            dist => panic!("{:?}", dist),
+        }
+        match "power:1K..1M".parse::<SizeDist>().unwrap() {
+            SizeDist::PowerLaw { alpha, .. } => assert_eq!(alpha, 1.0),
+            dist => panic!("{:?}", dist),
+        }
+    }
+
+    #[test]
+    fn test_parse_rejected() {
+        let rejected = [
+            "",
+            "4K",
+            "4K:70,:30",
+            ":30",
+            "4K:",
+            "-4K:70",
+            "0:70",
+            "4K:0",
+            "4K:x",
+            "power:..1M",
+            "power:1K..",
+            "power:1M..1K",
+            "power:-1K..1M",
+            "power:1K..1M:0",
+            "power:1K-1M",
+        ];
+        for s in rejected.iter() {
+            assert!(s.parse::<SizeDist>().is_err(), "{:?} accepted", s);
+        }
+    }
+
+    #[test]
+    fn test_sample_weighted() {
+        let dist = "4K:75,64K:25".parse::<SizeDist>().unwrap();
+        let mut rng = SmallRng::seed_from_u64(1);
+        let n = 10_000;
+        let small = (0..n)
+            .map(|_| dist.sample(&mut rng))
+            .inspect(|size| assert!(*size == 4096 || *size == 65536, "{}", size))
+            .filter(|size| *size == 4096)
+            .count();
+        assert!(small > n * 70 / 100 && small < n * 80 / 100, "{}", small);
+        assert_eq!(dist.max(), 65536);
+    }
+
+    #[test]
+    fn test_sample_power() {
+        let dist = "power:1K..1M".parse::<SizeDist>().unwrap();
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut sizes: Vec<isize> = (0..10_000).map(|_| dist.sample(&mut rng)).collect();
+        assert!(sizes.iter().all(|size| (1024..=1024 * 1024).contains(size)));
+        sizes.sort();
+        // most samples are close to the lower bound.
+        assert!(
+            sizes[sizes.len() / 2] < 4096,
+            "median {}",
+            sizes[sizes.len() / 2]
+        );
+        assert_eq!(dist.max(), 1024 * 1024);
+    }
+}
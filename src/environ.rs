@@ -0,0 +1,122 @@
+use std::{env, fs, io, path};
+
+use crate::results::quote;
+
+// Run environment, captured once at startup so that results from
+// different machines can be told apart.
+#[derive(Clone, Debug)]
+pub struct Environ {
+    pub os: String,
+    pub kernel: String,
+    pub cpu: String,
+    pub ram: u64, // bytes
+    pub version: String,
+    pub args: Vec<String>,
+}
+
+impl Environ {
+    pub fn probe(args: &[String]) -> Environ {
+        Environ {
+            os: os_name(),
+            kernel: kernel_version(),
+            cpu: cpu_model(),
+            ram: total_ram(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            args: args.to_vec(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let fields: Vec<String> = self
+            .fields()
+            .into_iter()
+            .map(|f| format!("  {}", f))
+            .collect();
+        format!("{{\n{}\n}}\n", fields.join(",\n"))
+    }
+
+    // as a single line, to embed in a results row.
+    pub fn to_json_line(&self) -> String {
+        format!("{{{}}}", self.fields().join(", "))
+    }
+
+    fn fields(&self) -> Vec<String> {
+        let args: Vec<String> = self.args.iter().map(|a| quote(a)).collect();
+        vec![
+            format!("\"os\": {}", quote(&self.os)),
+            format!("\"kernel\": {}", quote(&self.kernel)),
+            format!("\"cpu\": {}", quote(&self.cpu)),
+            format!("\"ram\": {}", self.ram),
+            format!("\"version\": {}", quote(&self.version)),
+            format!("\"args\": [{}]", args.join(", ")),
+        ]
+    }
+
+    pub fn write_json<P: AsRef<path::Path>>(&self, file: P) -> io::Result<()> {
+        fs::write(file, self.to_json())
+    }
+}
+
+// value of `key` from a `key: value` or `key=value` style text file.
+fn lookup(file: &str, key: &str, sep: char) -> Option<String> {
+    let text = fs::read_to_string(file).ok()?;
+    text.lines().find_map(|line| {
+        let mut parts = line.splitn(2, sep);
+        match (parts.next(), parts.next()) {
+            (Some(k), Some(v)) if k.trim() == key => Some(v.trim().trim_matches('"').to_string()),
+            _ => None,
+        }
+    })
+}
+
+fn os_name() -> String {
+    lookup("/etc/os-release", "PRETTY_NAME", '=').unwrap_or_else(|| env::consts::OS.to_string())
+}
+
+#[cfg(unix)]
+fn kernel_version() -> String {
+    use std::ffi::CStr;
+
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return "unknown".to_string();
+    }
+    let field = |f: &[libc::c_char]| {
+        unsafe { CStr::from_ptr(f.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    format!(
+        "{} {} {}",
+        field(&uts.sysname),
+        field(&uts.release),
+        field(&uts.machine)
+    )
+}
+
+#[cfg(not(unix))]
+fn kernel_version() -> String {
+    "unknown".to_string()
+}
+
+fn cpu_model() -> String {
+    let cpus = num_cpus();
+    match lookup("/proc/cpuinfo", "model name", ':') {
+        Some(model) => format!("{} x {}", cpus, model),
+        None => format!("{} x {}", cpus, env::consts::ARCH),
+    }
+}
+
+fn num_cpus() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+fn total_ram() -> u64 {
+    // MemTotal is reported in kB.
+    lookup("/proc/meminfo", "MemTotal", ':')
+        .and_then(|v| v.trim_end_matches("kB").trim().parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+        .unwrap_or(0)
+}
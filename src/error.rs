@@ -1,21 +1,70 @@
-use std::{error::Error, fmt, io, time};
+use std::{error, fmt, io, time};
 
-pub struct DiskioError(pub String);
+#[derive(Debug)]
+pub enum DiskioError {
+    Io(io::Error),
+    Time(time::SystemTimeError),
+    PartialWrite {
+        wrote: usize,
+        expected: usize,
+    },
+    DiskFull,
+    Config(String),
+    Smart(String),
+    Plot(String),
+    Unaligned {
+        what: &'static str,
+        size: isize,
+        align: isize,
+    },
+    Overflow(&'static str),
+    Injected(u64), // operations done before it
+}
 
 impl fmt::Display for DiskioError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        use DiskioError::*;
+
+        match self {
+            Io(err) => write!(f, "io error: {}", err),
+            Time(err) => write!(f, "time error: {}", err),
+            PartialWrite { wrote, expected } => {
+                write!(f, "partial write {} of {} bytes", wrote, expected)
+            }
+            DiskFull => write!(f, "no space left on device"),
+            Config(msg) => write!(f, "config error: {}", msg),
+            Smart(msg) => write!(f, "smart error: {}", msg),
+            Plot(msg) => write!(f, "unable to plot {}", msg),
+            Unaligned { what, size, align } => {
+                write!(f, "{} {} not aligned to {} for O_DIRECT", what, size, align)
+            }
+            Overflow(what) => write!(f, "{} overflows its integer type", what),
+            Injected(ops) => write!(f, "injected error after {} operations", ops),
+        }
+    }
+}
+
+impl error::Error for DiskioError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DiskioError::Io(err) => Some(err),
+            DiskioError::Time(err) => Some(err),
+            _ => None,
+        }
     }
 }
 
 impl From<io::Error> for DiskioError {
     fn from(err: io::Error) -> DiskioError {
-        DiskioError(err.description().to_string())
+        match err.kind() {
+            io::ErrorKind::StorageFull => DiskioError::DiskFull,
+            _ => DiskioError::Io(err),
+        }
     }
 }
 
 impl From<time::SystemTimeError> for DiskioError {
     fn from(err: time::SystemTimeError) -> DiskioError {
-        DiskioError(err.description().to_string())
+        DiskioError::Time(err)
     }
 }
@@ -0,0 +1,97 @@
+use std::{
+    io::{self, Write},
+    time,
+};
+
+use hdrhistogram::{
+    serialization::{interval_log, V2DeflateSerializer},
+    Histogram,
+};
+
+// HdrHistogram of latencies in microseconds, keeping 3 significant
+// digits, a thin wrapper over the hdrhistogram crate writing the
+// reference implementation's outputs, so that they read the same as
+// wrk, fio or YCSB output.
+const SIGNIFICANT_DIGITS: u8 = 3;
+const TICKS_PER_HALF_DISTANCE: u32 = 5;
+// sub-buckets per bucket for 3 significant digits.
+const SUB_BUCKET_COUNT: usize = 2048;
+
+pub fn histogram(latencies: &[u64]) -> Histogram<u64> {
+    let mut histogram = Histogram::new(SIGNIFICANT_DIGITS).unwrap();
+    // auto-resizes, it fails only for values beyond what it can track.
+    for l in latencies.iter() {
+        if histogram.record(*l).is_err() {
+            histogram.saturating_record(*l);
+        }
+    }
+    histogram
+}
+
+// percentile distribution in the text format of the reference
+// `outputPercentileDistribution`, values divided by `scale`, as read by
+// HistogramLogProcessor and hdr-plot.
+pub fn write_percentiles<W: Write>(h: &Histogram<u64>, w: &mut W, scale: f64) -> io::Result<()> {
+    let n = SIGNIFICANT_DIGITS as usize;
+    writeln!(
+        w,
+        "{:>12} {:>14} {:>10} {:>14}\n",
+        "Value", "Percentile", "TotalCount", "1/(1-Percentile)"
+    )?;
+    let mut count = 0;
+    for v in h.iter_quantiles(TICKS_PER_HALF_DISTANCE) {
+        count += v.count_since_last_iteration();
+        let value = (v.value_iterated_to() as f64) / scale;
+        let p = v.quantile_iterated_to();
+        if p < 1_f64 {
+            let inv = 1_f64 / (1_f64 - p);
+            writeln!(w, "{:12.*} {:2.12} {:10} {:14.2}", n, value, p, count, inv)?;
+        } else {
+            writeln!(w, "{:12.*} {:2.12} {:10}", n, value, p, count)?;
+        }
+    }
+    let max = if h.is_empty() { 0 } else { h.max() };
+    writeln!(
+        w,
+        "#[Mean    = {:12.*}, StdDeviation   = {:12.*}]",
+        n,
+        h.mean() / scale,
+        n,
+        h.stdev() / scale
+    )?;
+    writeln!(
+        w,
+        "#[Max     = {:12.*}, Total count    = {:12}]",
+        n,
+        (max as f64) / scale,
+        h.len()
+    )?;
+    writeln!(
+        w,
+        "#[Buckets = {:12}, SubBuckets     = {:12}]",
+        h.buckets(),
+        SUB_BUCKET_COUNT
+    )
+}
+
+// interval log, `.hlog`, holding the histogram as one interval of
+// `elapsed` ending now, compressed, as read by HistogramLogProcessor.
+pub fn write_log<W: Write>(
+    h: &Histogram<u64>,
+    w: &mut W,
+    elapsed: time::Duration,
+    tag: &str,
+) -> io::Result<()> {
+    let now = time::SystemTime::now();
+    let start = now.checked_sub(elapsed).unwrap_or(now);
+    let mut serializer = V2DeflateSerializer::new();
+    let mut log = interval_log::IntervalLogWriterBuilder::new()
+        .add_comment("diskio latencies in microseconds")
+        .with_start_time(start)
+        .with_base_time(start)
+        .with_max_value_divisor(1000_f64)
+        .begin_log_with(w, &mut serializer)?;
+    let tag = interval_log::Tag::new(tag);
+    log.write_histogram(h, time::Duration::default(), elapsed, tag)
+        .map_err(|err| io::Error::other(format!("{:?}", err)))
+}
@@ -0,0 +1,76 @@
+use std::{str::FromStr, time};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+// Latency to add to a fraction of operations, `<prob>:<micros>`, like
+// `0.01:50000` delays one in a hundred operations by 50ms.
+#[derive(Debug, Clone, Copy)]
+pub struct InjectLatency {
+    prob: f64,
+    delay: time::Duration,
+}
+
+impl FromStr for InjectLatency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<InjectLatency, Self::Err> {
+        let (prob, micros) = match s.find(':') {
+            Some(off) => (&s[..off], &s[off + 1..]),
+            None => return Err(format!("expected `prob:micros`, found `{}`", s)),
+        };
+        let prob = parse_prob(prob)?;
+        let micros = micros.parse::<u64>().map_err(|e| format!("{}", e))?;
+        let delay = time::Duration::from_micros(micros);
+        Ok(InjectLatency { prob, delay })
+    }
+}
+
+// probability between 0 and 1.
+pub fn parse_prob(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(prob) if (0.0..=1.0).contains(&prob) => Ok(prob),
+        Ok(_) => Err(format!("probability `{}` not within 0..1", s)),
+        Err(err) => Err(format!("invalid probability `{}`: {}", s, err)),
+    }
+}
+
+// Failure injection for writers, to check that latency reporting, stall
+// detection and alerting downstream fire. Draws come from an rng of its
+// own, seeded from --seed, so that a run is reproducible and offsets
+// written don't change with injection.
+pub struct Injector {
+    rng: SmallRng,
+    latency: Option<InjectLatency>,
+    error: Option<f64>,
+}
+
+impl Injector {
+    pub fn new(seed: u128, latency: Option<InjectLatency>, error: Option<f64>) -> Injector {
+        let seed = seed ^ 0x6469_736b_696f_696e_6a65_6374; // "diskioinject"
+                                                           // seed_from_u64 mixes the seed, seeds of threads that differ in a
+                                                           // bit or two must not yield correlated draws.
+        let seed = (seed as u64) ^ ((seed >> 64) as u64);
+        Injector {
+            rng: SmallRng::seed_from_u64(seed),
+            latency,
+            error,
+        }
+    }
+
+    // delay to add to this operation, if any.
+    pub fn latency(&mut self) -> Option<time::Duration> {
+        let latency = self.latency?;
+        match self.rng.gen_bool(latency.prob) {
+            true => Some(latency.delay),
+            false => None,
+        }
+    }
+
+    // whether this operation should fail.
+    pub fn error(&mut self) -> bool {
+        match self.error {
+            Some(prob) => self.rng.gen_bool(prob),
+            None => false,
+        }
+    }
+}
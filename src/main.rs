@@ -1,25 +1,81 @@
 use std::{
     convert::TryInto,
-    ffi, fs,
+    env, ffi, fs,
     io::{self, Read, Seek, Write},
-    path,
+    path, process,
     str::FromStr,
-    sync::atomic::{AtomicU64, Ordering},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     thread, time,
 };
 
+use chrono::TimeZone;
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 use regex::Regex;
-use structopt::StructOpt;
+use structopt::{
+    clap::{AppSettings, ErrorKind},
+    StructOpt,
+};
 #[macro_use]
 extern crate lazy_static;
 
+mod affinity;
+mod block;
+mod calibrate;
+mod commit;
+mod config;
+mod cpustat;
+mod diskstats;
+mod dist;
+mod environ;
 mod error;
+mod hdr;
+mod inject;
+mod manifest;
+mod observer;
 mod plot;
+#[cfg(unix)]
+mod replay;
+mod results;
+mod smart;
 mod stats;
+mod throttle;
+#[cfg(target_os = "linux")]
+mod uring;
+mod verify;
 
+use crate::block::Block;
+use crate::commit::Committer;
+use crate::config::Config;
+use crate::cpustat::{CpuSampler, CpuUsage};
+use crate::diskstats::{DiskStats, UtilSampler};
+use crate::dist::SizeDist;
+use crate::environ::Environ;
 use crate::error::DiskioError;
+use crate::inject::{InjectLatency, Injector};
+use crate::manifest::Artifact;
+use crate::observer::{NoopObserver, OpObserver};
 use crate::stats::Stats;
+use crate::throttle::Throttle;
+
+// block sizes swept when no workload is given.
+const DEFAULT_SWEEP: [isize; 3] = [4 * 1024, 64 * 1024, 1024 * 1024];
+
+// options that describe a workload, without any of them the default
+// sweep is run.
+const DEFAULT_SWEEP_ARGS: [&str; 9] = [
+    "block_size",
+    "appenders",
+    "writers",
+    "rangers",
+    "reverses",
+    "readers",
+    "fsync_only",
+    "many_files",
+    "async_files",
+];
 
 #[derive(Debug, StructOpt, Clone)]
 struct Opt {
@@ -51,9 +107,373 @@ struct Opt {
 
     #[structopt(long = "plot")]
     plot: bool,
+
+    #[structopt(long = "no-plot")]
+    no_plot: bool,
+
+    #[structopt(long = "compare-sync")]
+    compare_sync: bool,
+
+    #[structopt(long = "compare-flush")]
+    compare_flush: bool,
+
+    #[structopt(long = "sync-range")]
+    sync_range: bool,
+
+    #[structopt(long = "fua")]
+    fua: bool,
+
+    #[structopt(
+        long = "fill-byte",
+        default_value = "0xAB",
+        parse(try_from_str = "parse_hex_byte")
+    )]
+    fill_byte: u8,
+
+    #[structopt(long = "ramp-up", default_value = "0")]
+    ramp_up: u64,
+
+    #[structopt(long = "config")]
+    config: Option<String>,
+
+    #[structopt(long = "max-runtime", parse(try_from_str = "parse_duration"))]
+    max_runtime: Option<time::Duration>,
+
+    #[structopt(long = "smart")]
+    smart: Option<String>,
+
+    #[structopt(
+        long = "sample-interval",
+        default_value = "1s",
+        parse(try_from_str = "parse_duration")
+    )]
+    sample_interval: time::Duration,
+
+    #[structopt(long = "many-files", default_value = "0")]
+    many_files: usize,
+
+    #[structopt(long = "async-files", default_value = "0")]
+    async_files: usize,
+
+    #[structopt(long = "out-dir")]
+    out_dir: Option<String>,
+
+    #[structopt(long = "structured-output")]
+    structured_output: bool,
+
+    #[structopt(long = "prefill")]
+    prefill: bool,
+
+    #[structopt(long = "trim")]
+    trim: bool,
+
+    #[structopt(long = "fsync-only", parse(try_from_str = "SizeArg::to_positive"))]
+    fsync_only: Option<isize>,
+
+    #[structopt(long = "label")]
+    label: Option<String>,
+
+    #[structopt(long = "env-subtitle")]
+    env_subtitle: bool,
+
+    #[structopt(long = "watch")]
+    watch: bool,
+
+    #[structopt(long = "offset-align", parse(try_from_str = "SizeArg::to_positive"))]
+    offset_align: Option<isize>,
+
+    #[structopt(long = "vectored")]
+    vectored: Option<usize>,
+
+    #[structopt(long = "direct")]
+    direct: bool,
+
+    #[structopt(long = "format-check")]
+    format_check: bool,
+
+    #[structopt(long = "target-iops")]
+    target_iops: Option<u64>,
+
+    #[structopt(long = "pin")]
+    pin: bool,
+
+    #[structopt(long = "pwrite")]
+    pwrite: bool,
+
+    #[structopt(long = "top-slow", default_value = "0")]
+    top_slow: usize,
+
+    #[structopt(long = "cleanup")]
+    cleanup: bool,
+
+    #[structopt(long = "open-loop", requires = "rate")]
+    open_loop: bool,
+
+    #[structopt(long = "rate")]
+    rate: Option<u64>,
+
+    #[structopt(long = "resume")]
+    resume: bool,
+
+    #[structopt(long = "latency-log")]
+    latency_log: Option<String>,
+
+    #[structopt(long = "hdr-out")]
+    hdr_out: Option<String>,
+
+    #[structopt(long = "retries", default_value = "0")]
+    retries: usize,
+
+    #[structopt(long = "rw-mix", parse(try_from_str = "parse_percent"))]
+    rw_mix: Option<u8>,
+
+    #[structopt(long = "converge")]
+    converge: bool,
+
+    #[structopt(long = "drop-caches")]
+    drop_caches: bool,
+
+    #[structopt(long = "profile")]
+    profile: bool,
+
+    #[structopt(long = "tmpfile")]
+    tmpfile: bool,
+
+    #[structopt(long = "durability-markers")]
+    durability_markers: bool,
+
+    #[structopt(long = "reopen")]
+    reopen: bool,
+
+    #[structopt(long = "fresh-buffer")]
+    fresh_buffer: bool,
+
+    #[structopt(long = "prefault")]
+    prefault: bool,
+
+    #[structopt(long = "inject-latency")]
+    inject_latency: Option<InjectLatency>,
+
+    #[structopt(long = "inject-error", parse(try_from_str = "inject::parse_prob"))]
+    inject_error: Option<f64>,
+
+    #[structopt(long = "max-samples", default_value = "0")]
+    max_samples: usize,
+
+    #[structopt(long = "sample-rate", default_value = "1")]
+    sample_rate: u64,
+
+    #[structopt(long = "commit-interval", parse(try_from_str = "parse_duration"))]
+    commit_interval: Option<time::Duration>,
+
+    #[structopt(long = "per-thread-stats")]
+    per_thread_stats: bool,
+
+    #[structopt(long = "results-db")]
+    results_db: Option<String>,
+
+    #[structopt(long = "round-robin")]
+    round_robin: Option<u64>,
+
+    #[structopt(long = "threads")]
+    threads: Option<SizeArg>,
+
+    #[structopt(long = "find-knee")]
+    find_knee: bool,
+
+    #[structopt(long = "rated-bandwidth")]
+    rated_bandwidth: Option<f64>, // MB/sec
+
+    #[structopt(
+        long = "stall-threshold",
+        default_value = "100ms",
+        parse(try_from_str = "parse_duration")
+    )]
+    stall_threshold: time::Duration,
+
+    #[structopt(long = "file-size-dist")]
+    file_size_dist: Option<SizeDist>,
+
+    #[structopt(
+        long = "throughput-unit",
+        default_value = "bytes",
+        raw(possible_values = "&[\"bytes\", \"iops\"]")
+    )]
+    throughput_unit: String,
+
+    #[structopt(
+        long = "theme",
+        default_value = "light",
+        raw(possible_values = "&[\"light\", \"dark\"]")
+    )]
+    theme: String,
 }
 
 impl Opt {
+    // load options from command line, and from the --config file if one
+    // is supplied, command line options override config file options.
+    fn load() -> Opt {
+        let cli_args: Vec<String> = env::args().collect();
+        let file = cli_args.iter().enumerate().find_map(|(i, arg)| {
+            if arg == "--config" {
+                cli_args.get(i + 1).cloned()
+            } else {
+                arg.strip_prefix("--config=").map(|file| file.to_string())
+            }
+        });
+        let config = match file {
+            Some(file) => Config::load(&file).unwrap_or_else(|err| {
+                println!("{}", err);
+                process::exit(1)
+            }),
+            None => return Opt::from_matches(&Opt::clap().get_matches()),
+        };
+
+        let mut args = vec![cli_args[0].clone()];
+        args.extend(config.args);
+        args.extend_from_slice(&cli_args[1..]);
+        let app = || Opt::clap().setting(AppSettings::AllArgsOverrideSelf);
+        let matches = match (app().get_matches_from_safe(&args), config.path) {
+            (Ok(matches), _) => matches,
+            (Err(ref err), Some(path)) if err.kind == ErrorKind::MissingRequiredArgument => {
+                args.push(path);
+                app().get_matches_from(&args)
+            }
+            (Err(err), _) => err.exit(),
+        };
+        Opt::from_matches(&matches)
+    }
+
+    fn from_matches(matches: &structopt::clap::ArgMatches) -> Opt {
+        let mut opt = Opt::from_clap(matches);
+        let given = DEFAULT_SWEEP_ARGS
+            .iter()
+            .any(|arg| matches.occurrences_of(arg) > 0);
+        if !given {
+            opt.default_sweep();
+        }
+        if let (SizeArg::Range(x, y), false) = (&opt.block_size, opt.find_knee) {
+            let blocks: Vec<String> = opt
+                .clone()
+                .block_size
+                .get_blocks()
+                .into_iter()
+                .map(|b| humanize(b as usize))
+                .collect();
+            println!(
+                "block-size {}..{} snaps to [{}], use `n:m:step` for literal sizes",
+                humanize(*x as usize),
+                humanize(*y as usize),
+                blocks.join(", "),
+            );
+        }
+        opt
+    }
+
+    // a bare `diskio <path>` would only append with 10MB blocks, run a
+    // small sweep that exercises appends, writes and reads instead.
+    fn default_sweep(&mut self) {
+        self.block_size = SizeArg::List(DEFAULT_SWEEP.to_vec());
+        self.appenders = 1;
+        self.writers = 1;
+        self.readers = 1;
+        let blocks: Vec<String> = DEFAULT_SWEEP
+            .iter()
+            .map(|b| humanize(*b as usize))
+            .collect();
+        println!(
+            "no workload given, running a default sweep of {} blocks, \
+             1 appender, 1 writer and 1 reader, {}s per phase",
+            blocks.join(", "),
+            self.duration
+        );
+    }
+
+    // configurations to run, in order, as (block-size, pass, duration)
+    // slices. With --round-robin every block size runs for a slice of
+    // that many seconds per pass, so that under --max-runtime each one
+    // gets at least a partial measurement.
+    fn schedule(&self) -> Vec<(isize, u64, u64)> {
+        let blocks = self.clone().block_size.get_blocks();
+        let slice = match self.slice() {
+            Some(slice) => slice,
+            None => return blocks.into_iter().map(|b| (b, 0, self.duration)).collect(),
+        };
+        (0..self.passes())
+            .flat_map(|pass| {
+                let duration = slice.min(self.duration.saturating_sub(pass * slice));
+                blocks.iter().map(move |b| (*b, pass, duration))
+            })
+            .collect()
+    }
+
+    fn slice(&self) -> Option<u64> {
+        match self.round_robin {
+            Some(slice) if slice > 0 => Some(slice.min(self.duration.max(1))),
+            _ => None,
+        }
+    }
+
+    fn passes(&self) -> u64 {
+        match self.slice() {
+            Some(slice) => self.duration.div_ceil(slice).max(1),
+            None => 1,
+        }
+    }
+
+    // thread counts to run with --threads, a single run otherwise.
+    fn thread_counts(&self) -> Vec<Option<isize>> {
+        match self.threads.clone() {
+            Some(arg) => arg.get_counts().into_iter().map(Some).collect(),
+            None => vec![None],
+        }
+    }
+
+    // the same workload with `n` threads of every kind in use, appenders
+    // when none is.
+    fn with_threads(&self, n: isize) -> Opt {
+        let mut opt = self.clone();
+        if opt.nwriters() + opt.nreaders() == 0 {
+            opt.appenders = n;
+        }
+        for count in [
+            &mut opt.appenders,
+            &mut opt.writers,
+            &mut opt.rangers,
+            &mut opt.reverses,
+            &mut opt.readers,
+        ] {
+            if *count > 0 {
+                *count = n;
+            }
+        }
+        opt
+    }
+
+    // thread count of a run under --threads.
+    fn swept_threads(&self) -> Option<isize> {
+        self.threads.as_ref().map(|_| {
+            let counts = [
+                self.appenders,
+                self.writers,
+                self.rangers,
+                self.reverses,
+                self.readers,
+            ];
+            counts.iter().cloned().max().unwrap_or(0)
+        })
+    }
+
+    // --no-plot wins over --plot, say from a config file.
+    fn plotting(&self) -> bool {
+        self.plot && !self.no_plot
+    }
+
+    // directory for plots and other result artifacts.
+    fn out_dir(&self) -> &str {
+        self.out_dir.as_ref().unwrap_or(&self.path)
+    }
+
     fn nappenders(&self) -> isize {
         let xs = vec![
             1,
@@ -62,6 +482,7 @@ impl Opt {
             self.rangers,
             self.reverses,
             self.readers,
+            self.async_files as isize,
         ];
         xs.into_iter().max().unwrap()
     }
@@ -74,6 +495,236 @@ impl Opt {
         self.rangers + self.reverses + self.readers
     }
 
+    fn ramp_delay(&self, k: usize, n: usize) -> time::Duration {
+        match n {
+            0 => time::Duration::from_millis(0),
+            n => time::Duration::from_millis(self.ramp_up * (k as u64) / (n as u64)),
+        }
+    }
+
+    // with --pin, the k-th thread of a phase runs on the k-th allowed cpu.
+    fn pin_cpu(&self, k: usize) -> Option<usize> {
+        if self.pin {
+            let cpus = affinity::allowed_cpus();
+            cpus.get(k % cpus.len().max(1)).cloned()
+        } else {
+            None
+        }
+    }
+
+    // alignment for random offsets, defaults to block size.
+    fn offset_align(&self, block_size: usize) -> u64 {
+        let align = self.offset_align.unwrap_or(block_size as isize);
+        align.max(1) as u64
+    }
+
+    // check option combinations and platform support upfront, before
+    // anything is written.
+    fn validate(&self) -> Result<(), DiskioError> {
+        if self.direct {
+            self.validate_direct()?;
+        }
+        if self.durability_markers {
+            self.validate_markers()?;
+        }
+        if self.find_knee {
+            self.knee_bounds()?;
+        }
+        if self.async_files > 0 {
+            self.validate_async_files()?;
+        }
+        if self.fua {
+            self.validate_fua()?;
+        }
+        if self.sync_range {
+            self.validate_sync_range()?;
+        }
+        if self.inject_latency.is_some() || self.inject_error.is_some() {
+            self.validate_inject()?;
+        }
+        if self.tmpfile {
+            self.validate_tmpfile()?;
+        }
+        if self.structured_output {
+            self.validate_structured_output()?;
+        }
+        Ok(())
+    }
+
+    // O_DIRECT requires io sizes and file offsets to be multiples of the
+    // device sector, catch them here instead of an EINVAL at write time.
+    fn validate_direct(&self) -> Result<(), DiskioError> {
+        let check = |what, size: isize| match size % DIRECT_ALIGN {
+            0 => Ok(()),
+            _ => Err(DiskioError::Unaligned {
+                what,
+                size,
+                align: DIRECT_ALIGN,
+            }),
+        };
+        for bsize in self.clone().block_size.get_blocks() {
+            check("block size", bsize)?;
+            check("block size", self.append_block_size(bsize))?;
+        }
+        if let Some(align) = self.offset_align {
+            check("offset-align", align)?;
+        }
+        if let Some(dirty) = self.fsync_only {
+            check("fsync-only", dirty)?;
+        }
+        Ok(())
+    }
+
+    // injection happens only in writers, see writer_thread.
+    fn validate_inject(&self) -> Result<(), DiskioError> {
+        if self.writers == 0 || self.rw_mix.is_some() {
+            let msg = "--inject-latency and --inject-error need --writers, without --rw-mix";
+            return Err(DiskioError::Config(msg.to_string()));
+        }
+        Ok(())
+    }
+
+    // O_TMPFILE files are anonymous and can't be reopened by writers and
+    // readers, probe once so that unsupported filesystems fail upfront.
+    fn validate_tmpfile(&self) -> Result<(), DiskioError> {
+        if self.writers + self.nreaders() > 0 {
+            let msg = "--tmpfile supports only appenders and --many-files";
+            return Err(DiskioError::Config(msg.to_string()));
+        }
+        if self.reopen {
+            let msg = "--tmpfile files can't be reopened with --reopen";
+            return Err(DiskioError::Config(msg.to_string()));
+        }
+        fs::create_dir_all(&self.path)?;
+        let mut oo = fs::OpenOptions::new();
+        oo.write(true);
+        tmpfile(&mut oo).map_err(DiskioError::Config)?;
+        match oo.open(&self.path) {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                let msg = format!("--tmpfile not supported on `{}`: {}", self.path, err);
+                Err(DiskioError::Config(msg))
+            }
+        }
+    }
+
+    // markers are read back at block boundaries, `diskio verify` can't
+    // find them at arbitrary offsets.
+    fn validate_markers(&self) -> Result<(), DiskioError> {
+        if self.offset_align.is_some() || self.direct {
+            let msg = "--durability-markers can't be used with --offset-align or --direct";
+            return Err(DiskioError::Config(msg.to_string()));
+        }
+        for bsize in self.clone().block_size.get_blocks() {
+            if (bsize as usize) < verify::MARKER_SIZE {
+                let msg = format!(
+                    "--durability-markers needs blocks of at least {} bytes",
+                    verify::MARKER_SIZE
+                );
+                return Err(DiskioError::Config(msg));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_sync_range(&self) -> Result<(), DiskioError> {
+        if cfg!(target_os = "linux") {
+            Ok(())
+        } else {
+            let msg = "--sync-range needs sync_file_range, supported only on linux";
+            Err(DiskioError::Config(msg.to_string()))
+        }
+    }
+
+    fn validate_async_files(&self) -> Result<(), DiskioError> {
+        if cfg!(target_os = "linux") {
+            Ok(())
+        } else {
+            let msg = "--async-files needs io_uring, supported only on linux";
+            Err(DiskioError::Config(msg.to_string()))
+        }
+    }
+
+    fn validate_fua(&self) -> Result<(), DiskioError> {
+        if cfg!(target_os = "linux") {
+            Ok(())
+        } else {
+            let msg = "--fua needs pwritev2 with RWF_DSYNC, supported only on linux";
+            Err(DiskioError::Config(msg.to_string()))
+        }
+    }
+
+    // sync mode of a regular, non comparing, run.
+    fn sync_mode(&self) -> SyncMode {
+        match self.sync_range {
+            true => SyncMode::Range,
+            false => SyncMode::All,
+        }
+    }
+
+    // block sizes bounding the --find-knee search.
+    fn knee_bounds(&self) -> Result<(isize, isize), DiskioError> {
+        let (lo, hi) = match self.block_size.clone() {
+            SizeArg::Range(x, y) => (x, y),
+            SizeArg::List(sizes) => {
+                let lo = sizes.iter().cloned().min().unwrap_or(0);
+                (lo, sizes.into_iter().max().unwrap_or(0))
+            }
+        };
+        if lo < 1 || hi < lo * 2 {
+            let msg = "--find-knee needs a range of block sizes, like 4K..64M";
+            return Err(DiskioError::Config(msg.to_string()));
+        }
+        if self.threads.is_some() || self.round_robin.is_some() {
+            let msg = "--find-knee can't be used with --threads or --round-robin";
+            return Err(DiskioError::Config(msg.to_string()));
+        }
+        Ok((lo, hi))
+    }
+
+    fn validate_structured_output(&self) -> Result<(), DiskioError> {
+        if self.resume {
+            let msg = "--resume can't find earlier runs under --structured-output";
+            return Err(DiskioError::Config(msg.to_string()));
+        }
+        Ok(())
+    }
+
+    // with --structured-output, artifacts of this run go into their own
+    // timestamped directory under out-dir.
+    fn structure_output(&mut self, started: &chrono::DateTime<chrono::Local>) {
+        let dir = path::Path::new(self.out_dir())
+            .join(format!("diskio-{}", started.format("%Y%m%d-%H%M%S")));
+        self.out_dir = Some(dir.to_str().unwrap().to_string());
+    }
+
+    // note an artifact about to be written into out-dir, for the manifest.
+    fn record(&self, file: &path::Path, kind: &'static str, block_size: Option<isize>) {
+        manifest::record(Artifact {
+            file: file.to_path_buf(),
+            kind,
+            block_size,
+            readers: self.nreaders(),
+            writers: self.nwriters(),
+            duration: self.duration,
+        });
+    }
+
+    fn throughput_unit(&self) -> plot::Unit {
+        match self.throughput_unit.as_str() {
+            "iops" => plot::Unit::Iops,
+            _ => plot::Unit::Bytes,
+        }
+    }
+
+    // with --profile, time the syscalls of every PROFILE_EVERY-th operation.
+    fn profile_probe(&self, k: usize) -> Option<time::Instant> {
+        match self.profile && k.is_multiple_of(PROFILE_EVERY) {
+            true => Some(time::Instant::now()),
+            false => None,
+        }
+    }
+
     fn append_block_size(&self, block_size: isize) -> isize {
         if self.appenders == 0 {
             10 * 1024 * 1024
@@ -87,76 +738,171 @@ struct Context {
     opt: Opt,
     filename: ffi::OsString,
     fd: fs::File,
-    block: Vec<u8>,
+    block: Block,
     duration: time::Duration,
+    sync: SyncMode,
+    throttle: Option<Arc<Throttle>>,
+    commit: Option<Arc<Committer>>,
+    observer: Arc<dyn OpObserver>,
+    cpu: Option<usize>,
+    access: Access,
+    prepared: time::Duration, // allocating and filling the block
 }
 
-impl Context {
-    fn new_append(i: isize, bsize: isize, opt: Opt) -> Context {
-        let filename = Context::new_data_file(i, &opt).unwrap();
-        println!("creating file `{}` ..", filename.to_str().unwrap());
-        let fd = fs::OpenOptions::new()
-            .append(true)
-            .create_new(true)
-            .open(filename.clone())
-            .unwrap();
-        let duration = time::Duration::from_nanos(opt.duration * 1_000_000_000);
+// how a written block is pushed towards the device, File::flush is a
+// no-op for files and gives no durability, it is only measured to make
+// that visible. Range writes back only the block's byte range, without
+// file metadata or a device cache flush. Fua makes the write itself
+// durable, with RWF_DSYNC, and needs no sync.
+#[derive(Clone, Copy, PartialEq)]
+enum SyncMode {
+    None,
+    Flush,
+    Data,
+    All,
+    Range,
+    Fua,
+}
 
-        Context {
-            opt,
-            fd,
-            filename,
-            block: {
-                let mut block = Vec::with_capacity(bsize as usize);
-                block.resize(block.capacity(), 0xAB);
-                block
-            },
-            duration,
+impl SyncMode {
+    fn name(&self) -> &'static str {
+        match self {
+            SyncMode::None => "nosync",
+            SyncMode::Flush => "flush",
+            SyncMode::Data => "sync_data",
+            SyncMode::All => "sync_all",
+            SyncMode::Range => "sync_file_range",
+            SyncMode::Fua => "fua",
         }
     }
+}
 
-    fn new_write(i: isize, bsize: isize, opt: Opt) -> Context {
-        let filename = Context::open_data_file(i, &opt).unwrap();
-        let fd = fs::OpenOptions::new()
-            .write(true)
-            .open(filename.clone())
-            .unwrap();
-        let duration = time::Duration::from_nanos(opt.duration * 1_000_000_000);
+// open mode for a data file, each thread-kind opens it differently.
+#[derive(Clone, Copy)]
+enum Access {
+    Append,
+    Write,
+    Read,
+    ReadWrite,
+}
 
-        Context {
-            opt,
-            fd,
-            filename,
-            block: {
-                let mut block = Vec::with_capacity(bsize as usize);
-                block.resize(block.capacity(), 0xAB);
-                block
-            },
-            duration,
+// Build a thread Context, workload parameters that are not part of Opt,
+// or that differ between phases, are set here instead of being threaded
+// through the constructor.
+struct ContextBuilder {
+    id: isize,
+    opt: Opt,
+    block_size: isize,
+    sync: SyncMode,
+    throttle: Option<Arc<Throttle>>,
+    commit: Option<Arc<Committer>>,
+    observer: Arc<dyn OpObserver>,
+    cpu: Option<usize>,
+}
+
+impl ContextBuilder {
+    fn new(id: isize, opt: &Opt) -> ContextBuilder {
+        ContextBuilder {
+            id,
+            opt: opt.clone(),
+            block_size: 0,
+            sync: SyncMode::All,
+            throttle: None,
+            commit: None,
+            observer: Arc::new(NoopObserver),
+            cpu: None,
         }
     }
 
-    fn new_read(i: isize, bsize: isize, opt: Opt) -> Context {
-        let filename = Context::open_data_file(i, &opt).unwrap();
-        let fd = fs::OpenOptions::new()
-            .read(true)
-            .open(filename.clone())
-            .unwrap();
+    fn block_size(mut self, block_size: isize) -> ContextBuilder {
+        self.block_size = block_size;
+        self
+    }
+
+    fn sync(mut self, sync: SyncMode) -> ContextBuilder {
+        self.sync = sync;
+        self
+    }
+
+    fn throttle(mut self, throttle: Option<Arc<Throttle>>) -> ContextBuilder {
+        self.throttle = throttle;
+        self
+    }
+
+    fn commit(mut self, commit: Option<Arc<Committer>>) -> ContextBuilder {
+        self.commit = commit;
+        self
+    }
+
+    fn observer(mut self, observer: Arc<dyn OpObserver>) -> ContextBuilder {
+        self.observer = observer;
+        self
+    }
+
+    fn cpu(mut self, cpu: Option<usize>) -> ContextBuilder {
+        self.cpu = cpu;
+        self
+    }
+
+    fn build(self, access: Access) -> Context {
+        let (id, opt) = (self.id, self.opt);
+        let mut oo = fs::OpenOptions::new();
+        let filename = match access {
+            Access::Append if opt.tmpfile => {
+                println!("creating anonymous file in `{}` ..", opt.path);
+                oo.write(true);
+                tmpfile(&mut oo).unwrap();
+                opt.path.clone().into()
+            }
+            Access::Append => {
+                let filename = Context::new_data_file(id, &opt).unwrap();
+                println!("creating file `{}` ..", filename.to_str().unwrap());
+                oo.append(true).create_new(true);
+                filename
+            }
+            Access::Write => {
+                oo.write(true);
+                Context::open_data_file(id, &opt).unwrap()
+            }
+            Access::Read => {
+                oo.read(true);
+                Context::open_data_file(id, &opt).unwrap()
+            }
+            Access::ReadWrite => {
+                oo.read(true).write(true);
+                Context::open_data_file(id, &opt).unwrap()
+            }
+        };
+        if opt.direct {
+            direct_io(&mut oo);
+        }
+        let mut fd = oo.open(filename.clone()).unwrap();
+        if let (Access::Append, true) = (access, opt.durability_markers) {
+            let header = verify::Header::new(self.block_size as usize, opt.fill_byte, opt.seed);
+            fd.write_all(&header.encode()).unwrap();
+        }
         let duration = time::Duration::from_nanos(opt.duration * 1_000_000_000);
+        let begin = time::Instant::now();
+        let block = Block::new(self.block_size as usize, opt.fill_byte);
 
         Context {
+            block,
+            prepared: begin.elapsed(),
             opt,
             fd,
             filename,
-            block: {
-                let mut block = Vec::with_capacity(bsize as usize);
-                block.resize(block.capacity(), 0xAB);
-                block
-            },
             duration,
+            sync: self.sync,
+            throttle: self.throttle,
+            commit: self.commit,
+            observer: self.observer,
+            cpu: self.cpu,
+            access,
         }
     }
+}
 
+impl Context {
     fn new_data_file(id: isize, opt: &Opt) -> io::Result<ffi::OsString> {
         // create dir
         let mut p = path::PathBuf::new();
@@ -176,252 +922,2634 @@ impl Context {
         Ok(p.into())
     }
 
+    fn many_file(id: isize, k: usize, opt: &Opt) -> path::PathBuf {
+        let mut p = path::PathBuf::new();
+        p.push(&opt.path);
+        p.push(format!("diskio-{}-{}.file", id, k));
+        p
+    }
+
     fn drop_data_file(id: isize, opt: &Opt) {
         let mut p = path::PathBuf::new();
         p.push(&opt.path);
         p.push(format!("diskio-{}.data", id));
         fs::remove_file(p.as_path()).ok();
     }
+
+    // pin the calling thread to the context's cpu, and re-allocate the
+    // block from the pinned thread, so that first-touch places its pages
+    // on the numa node local to that cpu. The block is touched right
+    // away, a zero filled one is only mapped lazily and would otherwise
+    // be placed wherever its pages are first faulted in.
+    fn pin(mut self) -> Context {
+        let mut touched = false;
+        if let Some(cpu) = self.cpu {
+            match affinity::pin(cpu) {
+                Ok(()) => {
+                    let begin = time::Instant::now();
+                    self.block = Block::new(self.block.len(), self.opt.fill_byte);
+                    self.block.prefault();
+                    self.prepared += begin.elapsed();
+                    touched = true;
+                }
+                Err(err) => println!("unable to pin thread to cpu {}: {}", cpu, err),
+            }
+        }
+        if self.opt.prefault && !touched {
+            let begin = time::Instant::now();
+            self.block.prefault();
+            self.prepared += begin.elapsed();
+        }
+        self
+    }
+
+    // write the block at `fpos` using pwrite, without touching, and
+    // contending on, the shared file position.
+    #[cfg(unix)]
+    fn write_block_at(&mut self, fpos: u64) -> io::Result<usize> {
+        use std::os::unix::fs::FileExt;
+
+        match self.sync {
+            SyncMode::Fua => {
+                let slices = [io::IoSlice::new(self.block.as_slice())];
+                write_dsync(&self.fd, &slices, fpos as i64)
+            }
+            _ => self.fd.write_at(self.block.as_slice(), fpos),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn write_block_at(&mut self, fpos: u64) -> io::Result<usize> {
+        self.fd.seek(io::SeekFrom::Start(fpos))?;
+        self.fd.write(self.block.as_slice())
+    }
+
+    // per-operation buffer handling, a new block with --fresh-buffer and
+    // the durability marker, timed apart from the io.
+    fn prepare_block(&mut self, seq: u64) -> time::Duration {
+        if !self.opt.fresh_buffer && !self.opt.durability_markers {
+            return time::Duration::default();
+        }
+        let begin = time::Instant::now();
+        if self.opt.fresh_buffer {
+            self.block = Block::new(self.block.len(), self.opt.fill_byte);
+            if self.opt.prefault {
+                self.block.prefault();
+            }
+        }
+        if self.opt.durability_markers {
+            verify::mark(&mut self.block, seq);
+        }
+        begin.elapsed()
+    }
+
+    // open the data file afresh, the old fd is closed once the new one
+    // is open, with --reopen.
+    fn reopen(&mut self) -> io::Result<()> {
+        let mut oo = fs::OpenOptions::new();
+        match self.access {
+            Access::Append => oo.append(true),
+            Access::Write => oo.write(true),
+            Access::Read => oo.read(true),
+            Access::ReadWrite => oo.read(true).write(true),
+        };
+        if self.opt.direct {
+            direct_io(&mut oo);
+        }
+        self.fd = oo.open(&self.filename)?;
+        Ok(())
+    }
+
+    // sync the block just written at `fpos`.
+    fn sync_block(&mut self, fpos: u64) -> io::Result<()> {
+        match self.sync {
+            SyncMode::None => Ok(()),
+            SyncMode::Flush => self.fd.flush(),
+            SyncMode::Data => self.fd.sync_data(),
+            SyncMode::All => self.fd.sync_all(),
+            SyncMode::Range => sync_file_range(&self.fd, fpos, self.block.len() as u64),
+            SyncMode::Fua => Ok(()),
+        }
+    }
+
+    // write the block in a single syscall, with --vectored the block is
+    // gathered from `n` smaller slices using write_vectored.
+    fn write_block(&mut self) -> io::Result<usize> {
+        match self.opt.vectored {
+            Some(n) if n > 0 => {
+                let chunk = self.block.len().div_ceil(n);
+                let slices: Vec<io::IoSlice> = self
+                    .block
+                    .chunks(chunk.max(1))
+                    .map(io::IoSlice::new)
+                    .collect();
+                match self.sync {
+                    SyncMode::Fua => write_dsync(&self.fd, &slices, -1),
+                    _ => self.fd.write_vectored(&slices),
+                }
+            }
+            _ if self.sync == SyncMode::Fua => {
+                let slices = [io::IoSlice::new(self.block.as_slice())];
+                write_dsync(&self.fd, &slices, -1)
+            }
+            _ => self.fd.write(self.block.as_slice()),
+        }
+    }
+}
+
+// Data files of a block-size run, removed when dropped at the end of the
+// run, or when it returns an error. If the main thread panics and
+// unwinds through it, files are left behind for inspection, unless
+// --cleanup is set. A failed or panicked io thread doesn't unwind main,
+// its run ends as usual and removes the files.
+struct DataFiles<'a> {
+    opt: &'a Opt,
+}
+
+impl Drop for DataFiles<'_> {
+    fn drop(&mut self) {
+        if !thread::panicking() || self.opt.cleanup {
+            (0..self.opt.nappenders()).for_each(|i| Context::drop_data_file(i, self.opt));
+        }
+    }
+}
+
+impl Context {
+    fn path_latency_plot(opt: &Opt, block_size: isize) -> path::PathBuf {
+        Context::path_plot(opt, "latency", block_size)
+    }
+
+    fn path_throughput_plot(opt: &Opt, block_size: isize) -> path::PathBuf {
+        Context::path_plot(opt, "throughput", block_size)
+    }
+
+    // marks a block-size run as completed, for --resume.
+    fn path_done(opt: &Opt, block_size: isize) -> path::PathBuf {
+        let mut p = path::PathBuf::new();
+        p.push(opt.out_dir());
+        p.push(format!(
+            "diskio-{}Rx{}Wx{}x{}.done",
+            opt.nreaders(),
+            opt.nwriters(),
+            humanize(block_size.try_into().unwrap()),
+            opt.duration,
+        ));
+        p
+    }
+
+    fn path_thread_csv(opt: &Opt, phase: &str, id: usize, block_size: isize) -> path::PathBuf {
+        let mut p = path::PathBuf::new();
+        p.push(opt.out_dir());
+        p.push(format!(
+            "diskio-thread-{}-{}-{}Rx{}Wx{}x{}.csv",
+            phase,
+            id,
+            opt.nreaders(),
+            opt.nwriters(),
+            humanize(block_size.try_into().unwrap()),
+            opt.duration,
+        ));
+        opt.record(&p, "thread-csv", Some(block_size));
+        p
+    }
+
+    // `ext` is either `hgrm` or `hlog`.
+    fn path_hdr(opt: &Opt, dir: &str, phase: &str, block_size: isize, ext: &str) -> path::PathBuf {
+        let mut p = path::PathBuf::new();
+        p.push(dir);
+        p.push(format!(
+            "diskio-hdr-{}-{}Rx{}Wx{}x{}.{}",
+            phase.replace(' ', "-"),
+            opt.nreaders(),
+            opt.nwriters(),
+            humanize(block_size.try_into().unwrap()),
+            opt.duration,
+            ext,
+        ));
+        let kind = if ext == "hlog" { "hdr-log" } else { "hdr" };
+        opt.record(&p, kind, Some(block_size));
+        p
+    }
+
+    fn path_sweep(opt: &Opt, name: &str) -> path::PathBuf {
+        let mut p = path::PathBuf::new();
+        p.push(opt.out_dir());
+        p.push(format!(
+            "diskio-plot-sweep-{}-{}Rx{}Wx{}.png",
+            name,
+            opt.nreaders(),
+            opt.nwriters(),
+            opt.duration,
+        ));
+        opt.record(&p, "plot", None);
+        p
+    }
+
+    fn path_plot(opt: &Opt, name: &str, block_size: isize) -> path::PathBuf {
+        let mut p = path::PathBuf::new();
+        p.push(opt.out_dir());
+        p.push(format!(
+            "diskio-plot-{}-{}Rx{}Wx{}x{}.png",
+            name,
+            opt.nreaders(),
+            opt.nwriters(),
+            humanize(block_size.try_into().unwrap()),
+            opt.duration,
+        ));
+        opt.record(&p, "plot", Some(block_size));
+        p
+    }
+}
+
+const PROFILE_EVERY: usize = 100;
+
+// io size and offset alignment required by O_DIRECT, logical sector size.
+const DIRECT_ALIGN: isize = 512;
+
+#[cfg(target_os = "linux")]
+fn direct_io(oo: &mut fs::OpenOptions) {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    oo.custom_flags(libc::O_DIRECT);
+}
+
+#[cfg(not(target_os = "linux"))]
+fn direct_io(_oo: &mut fs::OpenOptions) {
+    println!("--direct is supported only on linux, using buffered io");
+}
+
+// open an unnamed file in the directory being opened, it never gets a
+// directory entry and is reclaimed on close.
+#[cfg(target_os = "linux")]
+fn tmpfile(oo: &mut fs::OpenOptions) -> Result<(), String> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    oo.custom_flags(libc::O_TMPFILE);
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn tmpfile(_oo: &mut fs::OpenOptions) -> Result<(), String> {
+    Err("--tmpfile is supported only on linux".to_string())
+}
+
+// pwritev2 flag, makes the write durable as O_DSYNC would. The kernel
+// sets FUA on the request where the device supports it, and falls back
+// to a write followed by a cache flush elsewhere.
+#[cfg(target_os = "linux")]
+const RWF_DSYNC: libc::c_int = 0x2;
+
+// write `slices` at `fpos` with RWF_DSYNC, at the file position when
+// `fpos` is -1. libc doesn't wrap pwritev2 yet, invoke the syscall.
+#[cfg(target_os = "linux")]
+fn write_dsync(fd: &fs::File, slices: &[io::IoSlice], fpos: i64) -> io::Result<usize> {
+    use std::os::unix::io::AsRawFd;
+
+    let n = unsafe {
+        libc::syscall(
+            libc::SYS_pwritev2,
+            fd.as_raw_fd() as libc::c_long,
+            slices.as_ptr() as *const libc::iovec,
+            slices.len() as libc::c_long,
+            fpos as libc::c_long,
+            0 as libc::c_long, // high word of fpos, unused on 64-bit
+            RWF_DSYNC as libc::c_long,
+        )
+    };
+    match n {
+        n if n < 0 => Err(io::Error::last_os_error()),
+        n => Ok(n as usize),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn write_dsync(_fd: &fs::File, _slices: &[io::IoSlice], _fpos: i64) -> io::Result<usize> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "pwritev2 not supported",
+    ))
+}
+
+// start write-back of the range and wait for it to complete.
+#[cfg(target_os = "linux")]
+fn sync_file_range(fd: &fs::File, offset: u64, len: u64) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let flags = libc::SYNC_FILE_RANGE_WRITE | libc::SYNC_FILE_RANGE_WAIT_AFTER;
+    let (offset, len) = (offset as libc::off64_t, len as libc::off64_t);
+    match unsafe { libc::sync_file_range(fd.as_raw_fd(), offset, len, flags) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sync_file_range(_fd: &fs::File, _offset: u64, _len: u64) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "sync_file_range not supported",
+    ))
+}
+
+// FITRIM ioctl, _IOWR('X', 121, struct fstrim_range), not in libc yet.
+#[cfg(target_os = "linux")]
+const FITRIM: libc::c_ulong = 0xc018_5879;
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct FstrimRange {
+    start: u64,
+    len: u64,
+    minlen: u64,
+}
+
+// discard all free space of the filesystem holding `path`, return the
+// number of bytes trimmed. Needs CAP_SYS_ADMIN.
+#[cfg(target_os = "linux")]
+fn fitrim(path: &str) -> io::Result<u64> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = fs::File::open(path)?;
+    let mut range = FstrimRange {
+        start: 0,
+        len: u64::MAX,
+        minlen: 0,
+    };
+    match unsafe { libc::ioctl(fd.as_raw_fd(), FITRIM, &mut range as *mut FstrimRange) } {
+        0 => Ok(range.len),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn fitrim(_path: &str) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "FITRIM supported only on linux",
+    ))
+}
+
+// block size of the filesystem holding `path`, or its nearest existing
+// ancestor.
+#[cfg(unix)]
+fn fs_block_size(path: &str) -> io::Result<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = path::Path::new(path)
+        .ancestors()
+        .find(|p| p.exists())
+        .unwrap_or_else(|| path::Path::new("."));
+    let cpath = CString::new(path.as_os_str().as_bytes())?;
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    match unsafe { libc::statvfs(cpath.as_ptr(), &mut st) } {
+        0 => Ok(st.f_bsize as u64),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(unix))]
+fn fs_block_size(_path: &str) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "statvfs not supported",
+    ))
+}
+
+// evict cached pages of `fd`, dirty pages are written back first.
+#[cfg(target_os = "linux")]
+fn fadvise_dontneed(fd: &fs::File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    fd.sync_data()?;
+    match unsafe { libc::posix_fadvise(fd.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) } {
+        0 => Ok(()),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn fadvise_dontneed(_fd: &fs::File) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "fadvise not supported",
+    ))
+}
+
+static W_TOTAL: AtomicU64 = AtomicU64::new(0);
+static R_TOTAL: AtomicU64 = AtomicU64::new(0);
+// once set, io threads stop at their next iteration and the sweep ends.
+static STOP: AtomicBool = AtomicBool::new(false);
+// io threads spawned and io threads that errored or paniced, across runs.
+static THREADS: AtomicU64 = AtomicU64::new(0);
+static FAILED: AtomicU64 = AtomicU64::new(0);
+// plots that could not be rendered, the run's stats are still reported.
+static PLOT_FAILED: AtomicU64 = AtomicU64::new(0);
+
+// once set, io threads of the current phase stop, with --converge.
+static CONVERGED: AtomicBool = AtomicBool::new(false);
+
+// One configuration of the block-size sweep, summarized for the sweep
+// matrix, rows are workload phases and columns are block sizes.
+struct SweepCell {
+    row: String,
+    threads: Option<isize>, // with --threads
+    block_size: isize,
+    rate: Option<f64>, // in --throughput-unit
+    mb_per_sec: Option<f64>,
+    iops: Option<f64>,
+    ops: u64,
+    bytes: u64,
+    p50: u64,
+    p99: u64,
+    p999: u64,
+    max: u64,
+    cpu: Option<CpuUsage>,
+}
+
+impl SweepCell {
+    // matrix row, phases are split by thread count under --threads.
+    fn label(&self) -> String {
+        match self.threads {
+            Some(n) => format!("{} x{}", self.row, n),
+            None => self.row.clone(),
+        }
+    }
+}
+
+lazy_static! {
+    static ref SWEEP: Mutex<Vec<SweepCell>> = Mutex::new(vec![]);
+}
+
+fn stopped() -> bool {
+    STOP.load(Ordering::Relaxed) || CONVERGED.load(Ordering::Relaxed)
+}
+
+fn main() {
+    // `diskio calibrate <path>`, use `./calibrate` for a path named so.
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 3 && args[1] == "calibrate" {
+        calibrate::run(&args[2]);
+        return;
+    }
+    // `diskio verify <file>`, check a file written with --durability-markers.
+    if args.len() == 3 && args[1] == "verify" {
+        verify::run(&args[2]).expect("unable to verify file");
+        return;
+    }
+    // `diskio latency-log <file>`, summarize a --latency-log file.
+    if args.len() == 3 && args[1] == "latency-log" {
+        print_latency_log(&args[2]).expect("unable to read latency log");
+        return;
+    }
+    // `diskio replay <trace> <file>`, replay a recorded io trace on file.
+    if args.len() == 4 && args[1] == "replay" {
+        #[cfg(unix)]
+        replay::run(&args[2], &args[3]).expect("unable to replay trace");
+        #[cfg(not(unix))]
+        println!("replay is supported only on unix");
+        return;
+    }
+
+    let mut opt = Opt::load();
+    let started = chrono::Local::now();
+    if opt.format_check {
+        let blocks = opt.clone().block_size.get_blocks();
+        let blocks: Vec<String> = blocks
+            .into_iter()
+            .map(|b| humanize(b.try_into().unwrap()))
+            .collect();
+        println!("{:?} expands to [{}]", opt.block_size, blocks.join(", "));
+        return;
+    }
+    opt.validate().unwrap_or_else(|err| {
+        println!("{}", err);
+        process::exit(1)
+    });
+    if let Some(file) = &opt.config {
+        println!("loaded workload from `{}` ..", file);
+    }
+    let environ = Environ::probe(&args);
+    println!(
+        "diskio {}, {}, {}, {}, {} RAM",
+        environ.version,
+        environ.os,
+        environ.kernel,
+        environ.cpu,
+        humanize(environ.ram as usize)
+    );
+    warn_fs_alignment(&opt);
+    if let Some(label) = &opt.label {
+        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S %z");
+        println!("label: {}", label);
+        println!("command: {}", args.join(" "));
+        println!("started: {}", timestamp);
+        plot::set_subtitle(format!("{}, {}", label, timestamp));
+    }
+    if opt.env_subtitle {
+        let env = format!("{}, {}", environ.kernel, environ.cpu);
+        match &opt.label {
+            Some(label) => plot::set_subtitle(format!("{}, {}", label, env)),
+            None => plot::set_subtitle(env),
+        }
+    }
+    plot::set_theme(&opt.theme);
+    if opt.watch || opt.converge {
+        stats::watch_latencies();
+    }
+    if opt.max_samples > 0 {
+        stats::max_samples(opt.max_samples);
+    }
+    if opt.sample_rate > 1 {
+        stats::sample_rate(opt.sample_rate);
+    }
+    if let Some(file) = &opt.latency_log {
+        stats::open_latency_log(file).expect("unable to create latency log");
+    }
+    if opt.pin {
+        let (cpus, nodes) = (affinity::allowed_cpus().len(), affinity::numa_nodes());
+        println!(
+            "pinning io threads across {} cpus, {} numa nodes",
+            cpus, nodes
+        );
+    }
+    if opt.structured_output {
+        opt.structure_output(&started);
+        println!("writing results into `{}` ..", opt.out_dir());
+    }
+    // out-dir defaults to the data path, which may not exist yet.
+    fs::create_dir_all(opt.out_dir()).expect("unable to create out-dir");
+    let env_file = path::Path::new(opt.out_dir()).join("diskio-env.json");
+    opt.record(&env_file, "env", None);
+    if let Err(err) = environ.write_json(&env_file) {
+        println!("unable to write `{}`: {}", env_file.to_str().unwrap(), err);
+    }
+    if let Some(max_runtime) = opt.max_runtime {
+        thread::spawn(move || {
+            thread::sleep(max_runtime);
+            println!("max-runtime {:?} exceeded, stopping ..", max_runtime);
+            STOP.store(true, Ordering::Relaxed);
+        });
+    }
+
+    let threads = match opt.find_knee {
+        true => {
+            do_find_knee(&opt, &environ);
+            vec![]
+        }
+        false => opt.thread_counts(),
+    };
+    for threads in threads {
+        let opt = match threads {
+            Some(n) => {
+                println!("running with {} threads ..", n);
+                opt.with_threads(n)
+            }
+            None => opt.clone(),
+        };
+        let passes = opt.passes();
+        for (bsize, pass, duration) in opt.schedule() {
+            if stopped() {
+                println!(
+                    "skipping block-size {}",
+                    humanize(bsize.try_into().unwrap())
+                );
+                continue;
+            }
+            let done = Context::path_done(&opt, bsize);
+            if opt.resume && done.exists() {
+                if pass == 0 {
+                    println!(
+                        "skipping block-size {}, completed in `{}`",
+                        humanize(bsize.try_into().unwrap()),
+                        done.to_str().unwrap()
+                    );
+                }
+                continue;
+            }
+            if passes > 1 {
+                println!(
+                    "round-robin pass {}/{}, block-size {} for {}s",
+                    pass + 1,
+                    passes,
+                    humanize(bsize.try_into().unwrap()),
+                    duration
+                );
+            }
+
+            let failed = FAILED.load(Ordering::Relaxed);
+            let recorded = SWEEP.lock().unwrap().len();
+            let slice = Opt {
+                duration,
+                ..opt.clone()
+            };
+            if do_block_size(bsize, &slice).is_err() {
+                process::exit(1);
+            }
+            if let Some(file) = &opt.results_db {
+                save_results(&slice, &environ, file, recorded);
+            }
+            println!();
+            if pass + 1 < passes {
+                continue;
+            }
+            if !stopped() && failed == FAILED.load(Ordering::Relaxed) {
+                let timestamp = chrono::Local::now().to_rfc3339();
+                fs::write(&done, timestamp).expect("unable to mark block-size done");
+                opt.record(&done, "done", Some(bsize));
+            }
+        }
+    }
+
+    stats::close_latency_log().expect("unable to write latency log");
+    log_scaling(&opt);
+    do_plot_sweep(&opt);
+    do_plot_scaling(&opt);
+    if opt.structured_output {
+        match manifest::write(opt.out_dir(), &started.to_rfc3339(), &environ) {
+            Ok(file) => println!("wrote manifest `{}`", file.to_str().unwrap()),
+            Err(err) => println!("unable to write manifest: {}", err),
+        }
+    }
+
+    // a failed thread stops early and its bytes are missing from the
+    // reported throughput, don't let such a run pass for a complete one.
+    let (failed, threads) = (
+        FAILED.load(Ordering::Relaxed),
+        THREADS.load(Ordering::Relaxed),
+    );
+    if failed > 0 {
+        println!("INCOMPLETE ({} of {} io threads failed)", failed, threads);
+        process::exit(1);
+    } else if stopped() {
+        println!("INCOMPLETE (stopped by max-runtime)");
+        process::exit(1);
+    }
+    let plot_failed = PLOT_FAILED.load(Ordering::Relaxed);
+    if plot_failed > 0 {
+        println!("{} plots failed to render", plot_failed);
+        process::exit(1);
+    }
+}
+
+// --find-knee stops doubling the block size once throughput improves by
+// less than KNEE_GAIN, then bisects at most KNEE_BISECTS times for the
+// smallest block size within KNEE_GAIN of the plateau.
+const KNEE_GAIN: f64 = 0.05;
+const KNEE_BISECTS: usize = 3;
+
+fn do_find_knee(opt: &Opt, environ: &Environ) {
+    let (lo, hi) = opt.knee_bounds().unwrap();
+    let mut runs: Vec<(isize, f64)> = vec![];
+    let mut measure = |bsize: isize| -> Option<f64> {
+        println!("find-knee, block-size {} ..", humanize(bsize as usize));
+        let recorded = SWEEP.lock().unwrap().len();
+        if do_block_size(bsize, opt).is_err() {
+            process::exit(1);
+        }
+        if let Some(file) = &opt.results_db {
+            save_results(opt, environ, file, recorded);
+        }
+        println!();
+        let rate = knee_rate(opt, recorded)?;
+        runs.push((bsize, rate));
+        Some(rate)
+    };
+
+    // double until throughput stops improving.
+    let (mut below, mut bsize) = (lo, lo);
+    let mut last = match measure(bsize) {
+        Some(rate) => rate,
+        None => return,
+    };
+    let (mut plateau, mut flat) = (last, false);
+    while bsize * 2 <= hi && !stopped() {
+        let rate = match measure(bsize * 2) {
+            Some(rate) => rate,
+            None => return,
+        };
+        plateau = plateau.max(rate);
+        if rate < last * (1_f64 + KNEE_GAIN) {
+            flat = true;
+            break;
+        }
+        below = bsize;
+        bsize *= 2;
+        last = rate;
+    }
+
+    // knee lies in (below, bsize], bisect it.
+    let mut knee = bsize;
+    for _ in 0..(if flat { KNEE_BISECTS } else { 0 }) {
+        let mid = ((below + knee) / 2 / DIRECT_ALIGN) * DIRECT_ALIGN;
+        if mid <= below || mid >= knee || stopped() {
+            break;
+        }
+        match measure(mid) {
+            Some(rate) if rate >= plateau * (1_f64 - KNEE_GAIN) => knee = mid,
+            Some(_) => below = mid,
+            None => break,
+        }
+    }
+
+    runs.sort_by_key(|(bsize, _)| *bsize);
+    let runs_s: Vec<String> = runs
+        .iter()
+        .map(|(b, rate)| format!("{}:{:.2}", humanize(*b as usize), rate))
+        .collect();
+    println!("find-knee throughput MB/sec, {}", runs_s.join("  "));
+    match flat {
+        true => println!(
+            "throughput knee at block-size {}, plateau {:.2} MB/sec, after {} runs",
+            humanize(knee as usize),
+            plateau,
+            runs.len()
+        ),
+        false => println!(
+            "throughput still improving at block-size {}, {:.2} MB/sec, no knee found",
+            humanize(bsize as usize),
+            plateau
+        ),
+    }
+}
+
+// MB/sec of the measured phase recorded since `from`, others when there
+// are writers or readers, append otherwise.
+fn knee_rate(opt: &Opt, from: usize) -> Option<f64> {
+    let phase = match opt.writers + opt.nreaders() {
+        0 => "append",
+        _ => "others",
+    };
+    let sweep = SWEEP.lock().unwrap();
+    sweep[from..]
+        .iter()
+        .rfind(|c| c.row.starts_with(phase))
+        .and_then(|c| c.mb_per_sec)
+}
+
+// writes that aren't a multiple of the filesystem block size make the
+// filesystem read-modify-write the blocks they partly cover.
+fn warn_fs_alignment(opt: &Opt) {
+    let fs_bsize = match fs_block_size(&opt.path) {
+        Ok(n) if n > 0 => n as isize,
+        _ => return,
+    };
+    let bsizes = opt.clone().block_size.get_blocks();
+    for bsize in bsizes.into_iter().filter(|b| b % fs_bsize != 0) {
+        println!(
+            "warning: block size {} is not a multiple of filesystem block size {}, \
+             expect read-modify-write penalty",
+            bsize, fs_bsize
+        );
+    }
+}
+
+fn print_latency_log(file: &str) -> Result<(), DiskioError> {
+    let records = stats::read_latency_log(file)?;
+    let mut latencies: Vec<u64> = records.iter().map(|(_, l)| *l).collect();
+    latencies.sort();
+    let (first, last) = match (
+        records.iter().map(|r| r.0).min(),
+        records.iter().map(|r| r.0).max(),
+    ) {
+        (Some(first), Some(last)) => (first, last),
+        _ => (0, 0),
+    };
+    println!(
+        "{} operations over {:?}",
+        latencies.len(),
+        time::Duration::from_micros(last - first)
+    );
+    for q in [0.50, 0.90, 0.99, 0.999].iter() {
+        let latency = stats::percentile(&latencies, *q);
+        println!(
+            "  p{:<5} {:?}",
+            q * 100_f64,
+            time::Duration::from_micros(latency)
+        );
+    }
+    let max = latencies.last().cloned().unwrap_or(0);
+    println!("  max    {:?}", time::Duration::from_micros(max));
+    Ok(())
+}
+
+// errors are reported here, and returned so that main can exit after
+// the data files are dropped.
+fn do_block_size(bsize: isize, opt: &Opt) -> Result<(), DiskioError> {
+    if opt.trim && !stopped() {
+        do_trim(opt).map_err(|err| {
+            println!("unable to trim `{}`: {}", opt.path, err);
+            err
+        })?;
+    }
+    if opt.many_files > 0 {
+        let ss = do_many_files(bsize, opt);
+        sweep_record(opt, "files", bsize, &ss);
+        do_plot_files(bsize, opt, ss);
+        return Ok(());
+    }
+
+    let _files = DataFiles { opt };
+    if let Some(dirty) = opt.fsync_only {
+        let ss = do_fsync_only(bsize, dirty, opt);
+        sweep_record(opt, "fsync", bsize, &ss);
+        do_plot(bsize, opt, ss, false);
+        return Ok(());
+    }
+    if opt.async_files > 0 {
+        let ss = do_async_files(bsize, opt);
+        sweep_record(opt, "async", bsize, &ss);
+        do_plot(bsize, opt, ss, true);
+        return Ok(());
+    }
+
+    let append_bsize = opt.append_block_size(bsize);
+    let mut modes = match (opt.compare_sync, opt.compare_flush) {
+        (_, true) => vec![SyncMode::Flush, SyncMode::Data, SyncMode::All],
+        (true, false) => vec![SyncMode::All, SyncMode::None],
+        (false, false) => vec![],
+    };
+    if opt.compare_flush && opt.sync_range {
+        modes.push(SyncMode::Range);
+    }
+    // --fua is measured against write+fsync.
+    if opt.fua {
+        if modes.is_empty() {
+            modes.push(SyncMode::All);
+        }
+        modes.push(SyncMode::Fua);
+    }
+    if !modes.is_empty() {
+        let (mut appends, mut others) = (vec![], vec![]);
+        for mode in modes.into_iter() {
+            println!("measuring with {} ..", mode.name());
+            appends.push((mode, do_append(bsize, opt, mode)));
+            others.push((mode, do_others(bsize, opt, mode)));
+        }
+        if opt.compare_flush || opt.fua {
+            log_compare_sync("append", &appends);
+            log_compare_sync("others", &others);
+        }
+        for (phase, runs) in [("append", &appends), ("others", &others)].iter() {
+            for (mode, ss) in runs.iter() {
+                sweep_record(opt, &format!("{} {}", phase, mode.name()), bsize, ss);
+            }
+        }
+        do_plot_compare(append_bsize, opt, appends);
+        do_plot_compare(bsize, opt, others);
+    } else {
+        let ss = do_append(bsize, opt, opt.sync_mode());
+        sweep_record(opt, "append", bsize, &ss);
+        do_plot(append_bsize, opt, ss, true);
+        if opt.prefill && !stopped() {
+            do_prefill(opt).map_err(|err| {
+                println!("unable to prefill data files: {}", err);
+                err
+            })?;
+        }
+        if !stopped() {
+            let ss = do_others(bsize, opt, opt.sync_mode());
+            sweep_record(opt, "others", bsize, &ss);
+            do_plot(bsize, opt, ss, opt.writers > 0);
+        }
+    }
+    Ok(())
+}
+
+fn do_append(bsize: isize, opt: &Opt, sync: SyncMode) -> Stats {
+    // io: append data
+    let mut threads = vec![];
+    let start_time = time::SystemTime::now();
+    let append_bsize = opt.append_block_size(bsize);
+    let dstats = DiskStats::sample(&opt.path);
+    let smart = smart_sample(opt);
+    let util = UtilSampler::start(opt.path.clone(), opt.sample_interval);
+    let cpu = CpuSampler::start(opt.sample_interval);
+    let n = opt.nappenders() as usize;
+    for i in 0..opt.nappenders() {
+        let ctxt = ContextBuilder::new(i, opt)
+            .block_size(append_bsize)
+            .sync(sync)
+            .cpu(opt.pin_cpu(threads.len()))
+            .build(Access::Append);
+        let delay = opt.ramp_delay(threads.len(), n);
+        threads.push(spawn_ramped(delay, move || append_thread(i, ctxt.pin())));
+    }
+    let mut ss = aggregate_threads(opt, threads);
+    let cpu = cpu.finish();
+    log_details(append_bsize, start_time, &ss);
+    log_rated(opt, start_time);
+    log_prepare(&ss);
+    log_threads(opt, "append", append_bsize, &ss);
+    log_top_slow(opt, start_time, &ss);
+    log_stalls(opt, &ss);
+    log_cliff(&ss);
+    log_profile(&ss);
+    log_coalescing(&opt.path, dstats.as_ref(), &ss);
+    log_write_amp(&opt.path, dstats.as_ref());
+    log_durability(&ss);
+    log_util(dstats.as_ref(), util.finish());
+    ss.cpu = log_cpu(cpu);
+    log_smart(opt, smart);
+    W_TOTAL.store(0, Ordering::Relaxed);
+    ss
+}
+
+fn do_others(bsize: isize, opt: &Opt, sync: SyncMode) -> Stats {
+    // io: other operations
+    if opt.drop_caches && opt.nreaders() > 0 {
+        do_drop_caches(opt).unwrap_or_else(|err| {
+            println!(
+                "warning: unable to drop caches, reads may hit memory: {}",
+                err
+            )
+        });
+    }
+    let mut threads = vec![];
+    let start_time = time::SystemTime::now();
+    let dstats = DiskStats::sample(&opt.path);
+    let smart = smart_sample(opt);
+    let util = UtilSampler::start(opt.path.clone(), opt.sample_interval);
+    let cpu = CpuSampler::start(opt.sample_interval);
+    let n = (opt.writers + opt.nreaders()) as usize;
+    let throttle = match (opt.open_loop, opt.rate, opt.target_iops) {
+        (true, Some(rate), _) => Some(Arc::new(Throttle::new_open_loop(rate))),
+        (_, _, Some(iops)) => Some(Arc::new(Throttle::new(iops))),
+        _ => None,
+    };
+    // with --commit-interval writers don't sync, a committer does.
+    let committer = match opt.commit_interval {
+        Some(interval) if opt.writers > 0 => {
+            let committer = Arc::new(new_committer(opt, interval));
+            let handle = committer.spawn();
+            Some((committer, handle))
+        }
+        _ => None,
+    };
+    let sync = if committer.is_some() {
+        SyncMode::None
+    } else {
+        sync
+    };
+    // embedders plug in their own instrumentation here.
+    let observer: Arc<dyn OpObserver> = Arc::new(NoopObserver);
+    for i in 0..opt.writers {
+        // with --pwrite all writers share the first data file.
+        let file_id = if opt.pwrite { 0 } else { i };
+        let ctxt = ContextBuilder::new(file_id, opt)
+            .block_size(bsize)
+            .sync(sync)
+            .throttle(throttle.clone())
+            .commit(committer.as_ref().map(|(c, _)| Arc::clone(c)))
+            .observer(Arc::clone(&observer))
+            .cpu(opt.pin_cpu(threads.len()));
+        let delay = opt.ramp_delay(threads.len(), n);
+        // with --rw-mix writers interleave reads with writes.
+        let handle = match opt.rw_mix {
+            Some(read_pct) => {
+                let ctxt = ctxt.build(Access::ReadWrite);
+                spawn_ramped(delay, move || mixed_thread(i, ctxt.pin(), read_pct))
+            }
+            None => {
+                let ctxt = ctxt.build(Access::Write);
+                spawn_ramped(delay, move || writer_thread(i, ctxt.pin()))
+            }
+        };
+        threads.push(handle);
+    }
+    for i in 0..opt.rangers {
+        let ctxt = ContextBuilder::new(i, opt)
+            .block_size(bsize)
+            .cpu(opt.pin_cpu(threads.len()))
+            .build(Access::Read);
+        let delay = opt.ramp_delay(threads.len(), n);
+        threads.push(spawn_ramped(delay, move || range_thread(i, ctxt.pin())));
+    }
+    for i in 0..opt.reverses {
+        let ctxt = ContextBuilder::new(i, opt)
+            .block_size(bsize)
+            .cpu(opt.pin_cpu(threads.len()))
+            .build(Access::Read);
+        let delay = opt.ramp_delay(threads.len(), n);
+        threads.push(spawn_ramped(delay, move || reverse_thread(i, ctxt.pin())));
+    }
+    for i in 0..opt.readers {
+        let ctxt = ContextBuilder::new(i, opt)
+            .block_size(bsize)
+            .cpu(opt.pin_cpu(threads.len()))
+            .build(Access::Read);
+        let delay = opt.ramp_delay(threads.len(), n);
+        threads.push(spawn_ramped(delay, move || reader_thread(i, ctxt.pin())));
+    }
+    let mut ss = aggregate_threads(opt, threads);
+    let cpu = cpu.finish();
+    let commits = committer.map(|(committer, handle)| {
+        committer.stop();
+        // the committer syncs for the writers, a failed sync leaves the
+        // run incomplete like a failed io thread.
+        THREADS.fetch_add(1, Ordering::Relaxed);
+        match handle.join() {
+            Ok(Ok(())) => (),
+            Ok(Err(err)) => {
+                println!("committer errored: {}", err);
+                FAILED.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(_) => {
+                println!("committer paniced");
+                FAILED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        committer.to_commits()
+    });
+    log_details(bsize, start_time, &ss);
+    log_rated(opt, start_time);
+    log_prepare(&ss);
+    log_threads(opt, "others", bsize, &ss);
+    log_top_slow(opt, start_time, &ss);
+    log_stalls(opt, &ss);
+    if opt.writers > 0 {
+        log_cliff(&ss);
+    }
+    log_iops(opt, bsize, start_time);
+    log_profile(&ss);
+    log_rw_mix(&ss);
+    log_commits(opt, &ss, commits);
+    log_coalescing(&opt.path, dstats.as_ref(), &ss);
+    log_write_amp(&opt.path, dstats.as_ref());
+    log_durability(&ss);
+    log_util(dstats.as_ref(), util.finish());
+    ss.cpu = log_cpu(cpu);
+    log_smart(opt, smart);
+    W_TOTAL.store(0, Ordering::Relaxed);
+    R_TOTAL.store(0, Ordering::Relaxed);
+    ss
+}
+
+// evict data files from page cache, so that reads hit the device. Running
+// as root, also drop the entire page cache via /proc/sys/vm/drop_caches.
+fn do_drop_caches(opt: &Opt) -> Result<(), DiskioError> {
+    for i in 0..opt.nappenders() {
+        let filename = Context::open_data_file(i, opt)?;
+        fadvise_dontneed(&fs::File::open(filename)?)?;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if unsafe { libc::geteuid() } == 0 {
+            match fs::write("/proc/sys/vm/drop_caches", "1") {
+                Ok(()) => println!("dropped page cache"),
+                Err(err) => println!("unable to drop page cache: {}", err),
+            }
+            return Ok(());
+        }
+    }
+    println!("dropped data files from page cache");
+    Ok(())
+}
+
+// discard the free space of the filesystem under test, including blocks
+// of data files left behind, so that every run starts on erased flash.
+fn do_trim(opt: &Opt) -> Result<(), DiskioError> {
+    let start_time = time::SystemTime::now();
+    fs::create_dir_all(&opt.path)?;
+    for i in 0..opt.nappenders() {
+        fs::remove_file(Context::open_data_file(i, opt)?).ok();
+    }
+    let trimmed = fitrim(&opt.path)?;
+    println!(
+        "trimmed {} on `{}` in {:?}",
+        humanize(trimmed.try_into().unwrap()),
+        opt.path,
+        start_time.elapsed()?
+    );
+    Ok(())
+}
+
+// rewrite data files sequentially, so that the measured pass overwrites
+// already allocated blocks. Not accounted in stats.
+fn do_prefill(opt: &Opt) -> Result<(), DiskioError> {
+    let start_time = time::SystemTime::now();
+    let block = vec![opt.fill_byte; 1024 * 1024];
+    let mut total = 0;
+    for i in 0..opt.nappenders() {
+        let filename = Context::open_data_file(i, opt)?;
+        let mut fd = fs::OpenOptions::new().write(true).open(filename)?;
+        let file_size = fd.metadata()?.len();
+        let mut fpos = 0;
+        while fpos < file_size {
+            let n = (file_size - fpos).min(block.len() as u64) as usize;
+            fd.write_all(&block[..n])?;
+            fpos += n as u64;
+        }
+        fd.sync_all()?;
+        total += file_size;
+    }
+    println!(
+        "prefill wrote {} in {:?}",
+        humanize(total.try_into().unwrap()),
+        start_time.elapsed()?
+    );
+    Ok(())
+}
+
+fn do_fsync_only(bsize: isize, dirty: isize, opt: &Opt) -> Stats {
+    // io: dirty `dirty` bytes and time a single fd.sync_all()
+    let mut threads = vec![];
+    let start_time = time::SystemTime::now();
+    let n = opt.nappenders() as usize;
+    for i in 0..opt.nappenders() {
+        let ctxt = ContextBuilder::new(i, opt)
+            .block_size(bsize)
+            .cpu(opt.pin_cpu(threads.len()))
+            .build(Access::Append);
+        let delay = opt.ramp_delay(threads.len(), n);
+        threads.push(spawn_ramped(delay, move || {
+            fsync_thread(i, ctxt.pin(), dirty)
+        }));
+    }
+    let ss = aggregate_threads(opt, threads);
+    log_details(bsize, start_time, &ss);
+    let mut latencies = ss.sync_latencies.clone();
+    latencies.sort();
+    println!(
+        "flushed {} dirty bytes {} times, fd.sync_all() p50 {:?} p99 {:?}",
+        humanize(dirty.try_into().unwrap()),
+        ss.syncs,
+        time::Duration::from_micros(stats::percentile(&latencies, 0.50)),
+        time::Duration::from_micros(stats::percentile(&latencies, 0.99)),
+    );
+    W_TOTAL.store(0, Ordering::Relaxed);
+    ss
+}
+
+fn do_async_files(bsize: isize, opt: &Opt) -> Stats {
+    // io: write+sync chains on many files, all driven from one thread
+    let start_time = time::SystemTime::now();
+    let chains: Vec<Context> = (0..(opt.async_files as isize))
+        .map(|i| {
+            ContextBuilder::new(i, opt)
+                .block_size(bsize)
+                .build(Access::Append)
+        })
+        .collect();
+    let cpu = CpuSampler::start(opt.sample_interval);
+    let threads = vec![thread::spawn(move || async_thread(chains))];
+    let mut ss = aggregate_threads(opt, threads);
+    let cpu = cpu.finish();
+    log_details(bsize, start_time, &ss);
+    log_rated(opt, start_time);
+    log_chains(&ss);
+    ss.cpu = log_cpu(cpu);
+    log_stalls(opt, &ss);
+    log_cliff(&ss);
+    W_TOTAL.store(0, Ordering::Relaxed);
+    ss
+}
+
+fn do_many_files(bsize: isize, opt: &Opt) -> Stats {
+    // io: create, write and sync many small files
+    fs::create_dir_all(&opt.path).expect("unable to create directory");
+    let mut threads = vec![];
+    let start_time = time::SystemTime::now();
+    let n = opt.appenders.max(1) as usize;
+    for i in 0..n {
+        let count = (opt.many_files / n) + if i < (opt.many_files % n) { 1 } else { 0 };
+        let opt = opt.clone();
+        let delay = opt.ramp_delay(threads.len(), n);
+        threads.push(spawn_ramped(delay, move || {
+            files_thread(i as isize, bsize, count, opt)
+        }));
+    }
+    let ss = aggregate_threads(opt, threads);
+    log_details(bsize, start_time, &ss);
+    println!(
+        "created {} files in {:?}",
+        ss.count,
+        start_time.elapsed().expect("failed to compute elapsed")
+    );
+    log_by_size(&ss);
+    W_TOTAL.store(0, Ordering::Relaxed);
+    ss
+}
+
+// stagger thread start by `delay`, so that load builds up gradually.
+fn spawn_ramped<F>(delay: time::Duration, f: F) -> thread::JoinHandle<Result<Stats, DiskioError>>
+where
+    F: 'static + Send + FnOnce() -> Result<Stats, DiskioError>,
+{
+    thread::spawn(move || {
+        thread::sleep(delay);
+        f()
+    })
+}
+
+fn aggregate_threads(
+    opt: &Opt,
+    threads: Vec<thread::JoinHandle<Result<Stats, DiskioError>>>, // active
+) -> Stats {
+    if (opt.watch || opt.converge) && !threads.is_empty() {
+        watch_threads(opt, &threads);
+    }
+
+    THREADS.fetch_add(threads.len() as u64, Ordering::Relaxed);
+    let mut aggr_stats = Stats::new(time::Duration::from_secs(1));
+    for (i, thread) in threads.into_iter().enumerate() {
+        match thread.join() {
+            Ok(res) => match res {
+                Ok(stat) if opt.per_thread_stats && stat.threads.is_empty() => {
+                    aggr_stats.threads.push(stat.clone());
+                    aggr_stats.join(stat)
+                }
+                Ok(stat) => aggr_stats.join(stat),
+                Err(err) => {
+                    println!("thread {} errored: {}", i, err);
+                    FAILED.fetch_add(1, Ordering::Relaxed);
+                    aggr_stats.thread_bytes.push(0);
+                }
+            },
+            Err(_) => {
+                println!("thread {} paniced", i);
+                FAILED.fetch_add(1, Ordering::Relaxed);
+                aggr_stats.thread_bytes.push(0);
+            }
+        }
+    }
+    CONVERGED.store(false, Ordering::Relaxed);
+    aggr_stats
+}
+
+// with --converge, p99 latency is stable once it changes by less than
+// CONVERGE_TOLERANCE for CONVERGE_CHECKS consecutive sample-intervals,
+// over at least CONVERGE_MIN_SAMPLES operations.
+const CONVERGE_TOLERANCE: f64 = 0.01;
+const CONVERGE_CHECKS: usize = 3;
+const CONVERGE_MIN_SAMPLES: usize = 1000;
+
+// print a refreshing status line, every sample-interval, until all
+// threads are done. With --converge, stop threads once p99 is stable.
+fn watch_threads(opt: &Opt, threads: &[thread::JoinHandle<Result<Stats, DiskioError>>]) {
+    let start = time::Instant::now();
+    let (mut w_last, mut r_last) = (0, 0);
+    let (mut seen, mut p99_last, mut stable) = (vec![], 0, 0);
+    stats::drain_recent();
+    while threads.iter().any(|t| !t.is_finished()) {
+        thread::sleep(opt.sample_interval);
+        let secs = opt.sample_interval.as_secs_f64();
+        let w_total = W_TOTAL.load(Ordering::Relaxed);
+        let r_total = R_TOTAL.load(Ordering::Relaxed);
+        let w_rate = (w_total.saturating_sub(w_last) as f64) / (1024_f64 * 1024_f64) / secs;
+        let r_rate = (r_total.saturating_sub(r_last) as f64) / (1024_f64 * 1024_f64) / secs;
+        let mut latencies = stats::drain_recent();
+        if opt.converge && !CONVERGED.load(Ordering::Relaxed) {
+            seen.extend_from_slice(&latencies);
+            seen.sort();
+            let p99 = stats::percentile(&seen, 0.99);
+            let change = (p99 as f64 - p99_last as f64).abs() / (p99_last.max(1) as f64);
+            stable = if change < CONVERGE_TOLERANCE {
+                stable + 1
+            } else {
+                0
+            };
+            p99_last = p99;
+            if stable >= CONVERGE_CHECKS && seen.len() >= CONVERGE_MIN_SAMPLES {
+                println!(
+                    "converged after {:?}, {} in {} ops, p99 {}us",
+                    start.elapsed(),
+                    humanize((w_total + r_total).try_into().unwrap()),
+                    seen.len(),
+                    p99
+                );
+                CONVERGED.store(true, Ordering::Relaxed);
+            }
+        }
+        if opt.watch {
+            latencies.sort();
+            print!(
+                "\r[{:>7.1}s] write {:>9.2} MB/s  read {:>9.2} MB/s  ops {:>8}  p99 {:>8}us",
+                start.elapsed().as_secs_f64(),
+                w_rate,
+                r_rate,
+                latencies.len(),
+                stats::percentile(&latencies, 0.99),
+            );
+            io::stdout().flush().ok();
+        }
+        w_last = w_total;
+        r_last = r_total;
+    }
+    if opt.watch {
+        println!();
+    }
+}
+
+fn log_details(bsize: isize, start: time::SystemTime, ss: &Stats) {
+    let elapsed = start.elapsed().expect("failed to compute elapsed");
+    let w_total: usize = W_TOTAL.load(Ordering::Relaxed).try_into().unwrap();
+    let r_total: usize = R_TOTAL.load(Ordering::Relaxed).try_into().unwrap();
+    let iops = |total: usize| (total as f64) / (bsize as f64) / elapsed.as_secs_f64();
+
+    if w_total > 0 {
+        println!(
+            "wrote {}, using {} blocks in {:?}, {:.0} IOPS",
+            humanize(w_total),
+            humanize(bsize.try_into().unwrap()),
+            elapsed,
+            iops(w_total),
+            // humanize(ss.file_size.try_into().unwrap()),
+        );
+    }
+    if r_total > 0 {
+        println!(
+            "readr {}, using {} blocks in {:?}, {:.0} IOPS",
+            humanize(r_total),
+            humanize(bsize.try_into().unwrap()),
+            elapsed,
+            iops(r_total),
+            // humanize(ss.file_size.try_into().unwrap()),
+        );
+    }
+    if ss.count > (ss.sync_latencies.len() as u64) {
+        println!(
+            "sampled {} of {} latencies, percentiles are estimates",
+            ss.sync_latencies.len(),
+            ss.count
+        );
+    }
+    if ss.thread_bytes.len() > 1 {
+        let xs: Vec<String> = ss
+            .thread_bytes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| format!("t{}:{}", i, humanize((*n).try_into().unwrap())))
+            .collect();
+        println!("per-thread {}", xs.join(" "));
+    }
+    if ss.retries > 0 {
+        println!("retried {} transient io errors", ss.retries);
+    }
+}
+
+fn log_coalescing(path: &str, before: Option<&DiskStats>, ss: &Stats) {
+    if ss.syncs == 0 {
+        return;
+    }
+    let after = DiskStats::sample(path);
+    let flushes = match (before, after.as_ref()) {
+        (Some(before), Some(after)) => after.flushes_since(before),
+        _ => None,
+    };
+    if let (Some(after), Some(flushes)) = (after, flushes) {
+        let ratio = (ss.syncs as f64) / (flushes.max(1) as f64);
+        println!(
+            "fsync coalescing on {}: {} syncs, {} device flushes, ratio {:.2}",
+            after.name, ss.syncs, flushes, ratio
+        );
+    }
+}
+
+// bytes the device wrote against bytes diskio wrote, the excess is the
+// filesystem's journal and metadata, writes still in the page cache
+// are not accounted.
+fn log_write_amp(path: &str, before: Option<&DiskStats>) {
+    let issued = W_TOTAL.load(Ordering::Relaxed);
+    let after = DiskStats::sample(path);
+    if let (Some(before), Some(after), true) = (before, after, issued > 0) {
+        let written = after.written_since(before);
+        println!(
+            "write amplification on {}: issued {}, device wrote {}, ratio {:.2}",
+            after.name,
+            humanize(issued as usize),
+            humanize(written as usize),
+            (written as f64) / (issued as f64)
+        );
+    }
+}
+
+// Below these, a synced write is faster than any device can make it
+// durable, most likely a volatile cache is acknowledging the flush.
+const MIN_SYNC_LATENCY: u64 = 20; // in microseconds
+const MAX_SYNC_BANDWIDTH: f64 = 8_f64 * 1024_f64 * 1024_f64 * 1024_f64; // bytes/sec
+
+fn log_durability(ss: &Stats) {
+    // skip when synced writes are mixed with other operations.
+    if ss.syncs == 0 || ss.syncs != ss.count {
+        return;
+    }
+    let mut latencies = ss.sync_latencies.clone();
+    latencies.sort();
+    let p50 = stats::percentile(&latencies, 0.50);
+    let op_size = (ss.bytes as f64) / (ss.syncs as f64);
+    let bandwidth = op_size * 1_000_000_f64 / (p50.max(1) as f64);
+    if p50 < MIN_SYNC_LATENCY || bandwidth > MAX_SYNC_BANDWIDTH {
+        println!(
+            "warning: p50 synced write latency {}us for {} writes is implausibly fast, \
+             flush is likely not durable (volatile write cache, tmpfs, or ignored flush)",
+            p50,
+            humanize(op_size as usize),
+        );
+    }
+}
+
+// print the slowest operations, with their offset into the run and wall
+// clock time, to correlate stalls with external events.
+fn log_top_slow(opt: &Opt, start: time::SystemTime, ss: &Stats) {
+    if opt.top_slow == 0 || ss.sync_latencies.is_empty() {
+        return;
+    }
+    let start = start.duration_since(time::UNIX_EPOCH).unwrap().as_micros() as u64;
+    let mut ops: Vec<(u64, u64)> = ss
+        .latency_times
+        .iter()
+        .cloned()
+        .zip(ss.sync_latencies.iter().cloned())
+        .collect();
+    ops.sort_by_key(|op| std::cmp::Reverse(op.1));
+    println!("slowest {} operations:", opt.top_slow.min(ops.len()));
+    for (at, latency) in ops.into_iter().take(opt.top_slow) {
+        let wall =
+            chrono::Local.timestamp((at / 1_000_000) as i64, ((at % 1_000_000) * 1000) as u32);
+        println!(
+            "  +{:>10.3}s  {}  {:?}",
+            (at.saturating_sub(start) as f64) / 1_000_000_f64,
+            wall.format("%H:%M:%S%.3f"),
+            time::Duration::from_micros(latency),
+        );
+    }
+}
+
+// with --per-thread-stats, latency summary of every thread, and its
+// samples saved as csv, to single out a slow thread.
+fn log_threads(opt: &Opt, phase: &str, bsize: isize, ss: &Stats) {
+    for (i, ts) in ss.threads.iter().enumerate() {
+        let mut latencies = ts.sync_latencies.clone();
+        latencies.sort();
+        println!(
+            "thread {:<3} {:>8} ops {:>8}  p50 {:?} p99 {:?} max {:?}",
+            i,
+            ts.count,
+            humanize(ts.bytes as usize),
+            time::Duration::from_micros(stats::percentile(&latencies, 0.50)),
+            time::Duration::from_micros(stats::percentile(&latencies, 0.99)),
+            time::Duration::from_micros(latencies.last().cloned().unwrap_or(0)),
+        );
+
+        let file = Context::path_thread_csv(opt, phase, i, bsize);
+        let mut text = "start_us,latency_us\n".to_string();
+        for (at, latency) in ts.latency_times.iter().zip(ts.sync_latencies.iter()) {
+            text.push_str(&format!("{},{}\n", at, latency));
+        }
+        if let Err(err) = fs::write(&file, text) {
+            println!("unable to write `{}`: {}", file.to_str().unwrap(), err);
+        }
+    }
+}
+
+// write throughput as a fraction of the device's --rated-bandwidth.
+fn log_rated(opt: &Opt, start: time::SystemTime) {
+    let w_total = W_TOTAL.load(Ordering::Relaxed);
+    match opt.rated_bandwidth {
+        Some(rated) if rated > 0_f64 && w_total > 0 => {
+            let elapsed = start.elapsed().expect("failed to compute elapsed");
+            let rate = (w_total as f64) / (1024_f64 * 1024_f64) / elapsed.as_secs_f64();
+            println!(
+                "reached {:.0}% of rated {} MB/s, at {:.2} MB/s",
+                rate / rated * 100_f64,
+                rated,
+                rate
+            );
+        }
+        _ => (),
+    }
+}
+
+// cpu-side buffer handling, a confound when benchmarking small writes.
+fn log_prepare(ss: &Stats) {
+    if ss.refill.is_zero() {
+        println!("buffer preparation {:?}, one-time", ss.prepare);
+        return;
+    }
+    let per_op =
+        time::Duration::from_nanos((ss.refill.as_nanos() / (ss.count.max(1) as u128)) as u64);
+    let mean = match ss.sync_latencies.len() {
+        0 => 0_f64,
+        n => (ss.sync_latencies.iter().sum::<u64>() as f64) / (n as f64),
+    };
+    println!(
+        "buffer preparation {:?} once, {:?} per op, {:.1}% of mean latency",
+        ss.prepare,
+        per_op,
+        (per_op.as_micros() as f64) / mean.max(1_f64) * 100_f64
+    );
+}
+
+fn log_chains(ss: &Stats) {
+    for (i, cs) in ss.threads.iter().enumerate() {
+        let mut latencies = cs.sync_latencies.clone();
+        latencies.sort();
+        println!(
+            "chain {:<3} {:>8} ops {:>8}  p50 {:?} p99 {:?} max {:?}",
+            i,
+            cs.count,
+            humanize(cs.bytes as usize),
+            time::Duration::from_micros(stats::percentile(&latencies, 0.50)),
+            time::Duration::from_micros(stats::percentile(&latencies, 0.99)),
+            time::Duration::from_micros(latencies.last().cloned().unwrap_or(0)),
+        );
+    }
+}
+
+// operations slower than --stall-threshold, typically the device pausing
+// for garbage collection under sustained load.
+fn log_stalls(opt: &Opt, ss: &Stats) {
+    let threshold: u64 = opt.stall_threshold.as_micros().try_into().unwrap();
+    let stalls: Vec<u64> = ss
+        .sync_latencies
+        .iter()
+        .cloned()
+        .filter(|latency| *latency > threshold)
+        .collect();
+    if stalls.is_empty() {
+        println!("no stalls over {:?}", opt.stall_threshold);
+        return;
+    }
+    println!(
+        "stalls over {:?}: {} ops, {:?} stalled, longest {:?}",
+        opt.stall_threshold,
+        stalls.len(),
+        time::Duration::from_micros(stalls.iter().sum()),
+        time::Duration::from_micros(stalls.iter().cloned().max().unwrap()),
+    );
+}
+
+// latency of operations bucketed by power-of-two size.
+fn log_by_size(ss: &Stats) {
+    if ss.op_sizes.is_empty() {
+        return;
+    }
+    let mut buckets: Vec<(u64, Vec<u64>)> = vec![];
+    for (size, latency) in ss.op_sizes.iter().zip(ss.sync_latencies.iter()) {
+        let bucket = size.next_power_of_two();
+        match buckets.iter_mut().find(|(b, _)| *b == bucket) {
+            Some((_, latencies)) => latencies.push(*latency),
+            None => buckets.push((bucket, vec![*latency])),
+        }
+    }
+    buckets.sort_by_key(|(bucket, _)| *bucket);
+    println!("latency by file size:");
+    for (bucket, mut latencies) in buckets.into_iter() {
+        latencies.sort();
+        println!(
+            "  <={:<6} {:>8} files  p50 {:?} p99 {:?}",
+            humanize(bucket as usize),
+            latencies.len(),
+            time::Duration::from_micros(stats::percentile(&latencies, 0.50)),
+            time::Duration::from_micros(stats::percentile(&latencies, 0.99)),
+        );
+    }
+}
+
+fn log_commits(opt: &Opt, ss: &Stats, commits: Option<(u64, Vec<u64>)>) {
+    let (commits, mut latencies) = match commits {
+        Some((commits, latencies)) if commits > 0 => (commits, latencies),
+        Some(_) => {
+            println!("group commit: no commits completed");
+            return;
+        }
+        None => return,
+    };
+    latencies.sort();
+    println!(
+        "group commit every {:?}: {} commits, {:.1} writes per commit, commit p50 {:?} p99 {:?}",
+        opt.commit_interval.unwrap(),
+        commits,
+        (ss.count as f64) / (commits as f64),
+        time::Duration::from_micros(stats::percentile(&latencies, 0.50)),
+        time::Duration::from_micros(stats::percentile(&latencies, 0.99)),
+    );
+}
+
+fn log_profile(ss: &Stats) {
+    let p = &ss.profile;
+    if p.ops == 0 {
+        return;
+    }
+    let n = p.ops as u32;
+    println!(
+        "profile of {} sampled ops: seek {:?} / write {:?} / sync {:?}",
+        p.ops,
+        p.seek / n,
+        p.write / n,
+        p.sync / n,
+    );
+}
+
+fn log_rw_mix(ss: &Stats) {
+    let (nr, nw) = (ss.read_latencies.len(), ss.write_latencies.len());
+    if nr + nw == 0 {
+        return;
+    }
+    let read_pct = (nr as f64) * 100_f64 / ((nr + nw) as f64);
+    println!(
+        "rw-mix reads {:.1}% writes {:.1}%",
+        read_pct,
+        100_f64 - read_pct
+    );
+    for (name, latencies) in [("read", &ss.read_latencies), ("write", &ss.write_latencies)].iter() {
+        let mut latencies = latencies.to_vec();
+        latencies.sort();
+        println!(
+            "  {:<5} p50 {:?} p99 {:?}",
+            name,
+            time::Duration::from_micros(stats::percentile(&latencies, 0.50)),
+            time::Duration::from_micros(stats::percentile(&latencies, 0.99)),
+        );
+    }
+}
+
+// a sustained drop in write throughput, on consumer SSDs the point where
+// the SLC cache is exhausted and writes go at native TLC/QLC speed.
+fn log_cliff(ss: &Stats) {
+    let at = match stats::cliff(&ss.throughputs) {
+        Some(at) => at,
+        None => return,
+    };
+    let secs = ss.interval.as_secs_f64();
+    let mb_per_sec = |values: &[u64]| {
+        let mean = (values.iter().sum::<u64>() as f64) / (values.len() as f64);
+        mean / secs / (1024_f64 * 1024_f64)
+    };
+    let cached: u64 = ss.throughputs[..at].iter().sum();
+    println!(
+        "throughput cliff after {:.0}s: SLC cache ~{}, post-cache throughput ~{:.2} MB/s, down from {:.2} MB/s",
+        (at as f64) * secs,
+        humanize(cached as usize),
+        mb_per_sec(&ss.throughputs[at..]),
+        mb_per_sec(&ss.throughputs[..at]),
+    );
+}
+
+fn log_iops(opt: &Opt, bsize: isize, start_time: time::SystemTime) {
+    let target = if opt.open_loop {
+        opt.rate
+    } else {
+        opt.target_iops
+    };
+    if let (Some(target), true) = (target, opt.writers > 0) {
+        let ops = W_TOTAL.load(Ordering::Relaxed) / (bsize as u64);
+        let secs = start_time.elapsed().unwrap().as_secs_f64();
+        println!(
+            "target {} write IOPS, achieved {:.0} IOPS",
+            target,
+            (ops as f64) / secs
+        );
+    }
+}
+
+fn log_util(dstats: Option<&DiskStats>, utils: Vec<f64>) {
+    if let (Some(dstats), false) = (dstats, utils.is_empty()) {
+        let avg = utils.iter().sum::<f64>() / (utils.len() as f64);
+        let peak = utils.iter().cloned().fold(0_f64, f64::max);
+        println!(
+            "disk utilization on {}: avg {:.1}% peak {:.1}%",
+            dstats.name, avg, peak
+        );
+    }
+}
+
+// high iowait with an idle cpu says the device is the bottleneck, high
+// system time says syscall overhead dominates.
+fn log_cpu((avg, usages): (Option<CpuUsage>, Vec<CpuUsage>)) -> Option<CpuUsage> {
+    let avg = avg?;
+    let peak = |f: fn(&CpuUsage) -> f64| usages.iter().map(f).fold(0_f64, f64::max);
+    println!(
+        "cpu: avg user {:.1}% system {:.1}% iowait {:.1}%, peak system {:.1}% iowait {:.1}%",
+        avg.user,
+        avg.system,
+        avg.iowait,
+        peak(|u| u.system),
+        peak(|u| u.iowait),
+    );
+    Some(avg)
+}
+
+fn smart_sample(opt: &Opt) -> Option<u64> {
+    let device = opt.smart.as_ref()?;
+    match smart::bytes_written(device) {
+        Ok(n) => Some(n),
+        Err(err) => {
+            println!("{}", err);
+            None
+        }
+    }
+}
+
+fn log_smart(opt: &Opt, before: Option<u64>) {
+    let w_total = W_TOTAL.load(Ordering::Relaxed);
+    if let (Some(before), Some(after)) = (before, smart_sample(opt)) {
+        if w_total > 0 {
+            let device_bytes = after.saturating_sub(before);
+            let waf = (device_bytes as f64) / (w_total as f64);
+            println!(
+                "smart: device wrote {} for {} written, write-amplification {:.2}",
+                humanize(device_bytes.try_into().unwrap()),
+                humanize(w_total.try_into().unwrap()),
+                waf
+            );
+        }
+    }
+}
+
+fn plot_failed(what: &str, err: Box<dyn std::error::Error>) {
+    let err = DiskioError::Plot(format!("{}: {}", what, err));
+    println!("warning: {}", err);
+    PLOT_FAILED.fetch_add(1, Ordering::Relaxed);
+}
+
+// `writes` for phases that write, only they are checked for a
+// throughput cliff, like log_cliff.
+fn do_plot(bsize: isize, opt: &Opt, ss: Stats, writes: bool) {
+    let cliff = match writes {
+        true => stats::cliff(&ss.throughputs),
+        false => None,
+    };
+    // offsets are recorded only by the io threads of the others phase.
+    if opt.plotting() && !ss.offsets.is_empty() && ss.offsets.len() == ss.sync_latencies.len() {
+        plot::latency_vs_offset(
+            Context::path_plot(opt, "latency-offset", bsize),
+            format!(
+                "latency vs offset, block-size:{}, wr:{}, rd:{}",
+                humanize(bsize.try_into().unwrap()),
+                opt.nwriters(),
+                opt.nreaders(),
+            ),
+            &ss.offsets,
+            &ss.sync_latencies,
+        )
+        .unwrap_or_else(|err| plot_failed("latency vs offset", err));
+    }
+    if opt.plotting() && !ss.threads.is_empty() {
+        let series = ss
+            .threads
+            .iter()
+            .enumerate()
+            .map(|(i, ts)| (format!("t{}", i), ts.sync_latencies.clone()))
+            .collect();
+        plot::latency_compare(
+            Context::path_plot(opt, "thread-latency", bsize),
+            format!(
+                "latency by thread, block-size:{}, wr:{}, rd:{}",
+                humanize(bsize.try_into().unwrap()),
+                opt.nwriters(),
+                opt.nreaders(),
+            ),
+            series,
+        )
+        .unwrap_or_else(|err| plot_failed("thread latency", err));
+    }
+    if opt.plotting() {
+        plot::latency_bands(
+            Context::path_plot(opt, "latency-bands", bsize),
+            format!(
+                "latency percentiles over time, block-size:{}, wr:{}, rd:{}",
+                humanize(bsize.try_into().unwrap()),
+                opt.nwriters(),
+                opt.nreaders(),
+            ),
+            &ss.latency_times,
+            &ss.sync_latencies,
+        )
+        .unwrap_or_else(|err| plot_failed("latency bands", err));
+
+        plot::latency_heatmap(
+            Context::path_plot(opt, "latency-heatmap", bsize),
+            format!(
+                "latency heatmap, block-size:{}, wr:{}, rd:{}",
+                humanize(bsize.try_into().unwrap()),
+                opt.nwriters(),
+                opt.nreaders(),
+            ),
+            &ss.latency_times,
+            &ss.sync_latencies,
+        )
+        .unwrap_or_else(|err| plot_failed("latency heatmap", err));
+
+        plot::percentile_convergence(
+            Context::path_plot(opt, "latency-convergence", bsize),
+            format!(
+                "percentile convergence, block-size:{}, wr:{}, rd:{}",
+                humanize(bsize.try_into().unwrap()),
+                opt.nwriters(),
+                opt.nreaders(),
+            ),
+            &ss.latency_times,
+            &ss.sync_latencies,
+        )
+        .unwrap_or_else(|err| plot_failed("percentile convergence", err));
+
+        plot::latency(
+            Context::path_latency_plot(opt, bsize),
+            format!(
+                "fd.sync_all() latency, block-size:{}, wr:{}, rd:{}",
+                humanize(bsize.try_into().unwrap()),
+                opt.nwriters(),
+                opt.nreaders(),
+            ),
+            ss.sync_latencies,
+        )
+        .unwrap_or_else(|err| plot_failed("latency", err));
+
+        plot::throughput(
+            Context::path_throughput_plot(opt, bsize),
+            format!(
+                "throughput for block-size:{}, wr:{}, rd:{}",
+                humanize(bsize.try_into().unwrap()),
+                opt.nwriters(),
+                opt.nreaders(),
+            ),
+            ss.interval,
+            opt.throughput_unit(),
+            match opt.throughput_unit() {
+                plot::Unit::Iops => ss.ops,
+                plot::Unit::Bytes => ss.throughputs,
+            },
+            cliff,
+        )
+        .unwrap_or_else(|err| plot_failed("throughput", err));
+    }
+}
+
+fn do_plot_files(bsize: isize, opt: &Opt, ss: Stats) {
+    if opt.plotting() {
+        plot::latency_histogram(
+            Context::path_plot(opt, "files-latency", bsize),
+            format!(
+                "create+write+sync latency, {} files, file-size:{}",
+                ss.count,
+                humanize(bsize.try_into().unwrap()),
+            ),
+            ss.sync_latencies,
+        )
+        .unwrap_or_else(|err| plot_failed("latency histogram", err));
+    }
 }
 
-impl Context {
-    fn path_latency_plot(opt: &Opt, block_size: isize) -> path::PathBuf {
-        let mut p = path::PathBuf::new();
-        p.push(&opt.path);
-        p.push(format!(
-            "diskio-plot-latency-{}Rx{}Wx{}x{}.png",
-            opt.nreaders(),
-            opt.nwriters(),
-            humanize(block_size.try_into().unwrap()),
-            opt.duration,
-        ));
-        p
+// tabulate write latency of the same workload under each sync mode.
+fn log_compare_sync(phase: &str, runs: &[(SyncMode, Stats)]) {
+    println!("{} write latency by sync mode:", phase);
+    println!(
+        "  {:<15} {:>12} {:>12} {:>12} {:>12}",
+        "mode", "p50", "p99", "p99.9", "max"
+    );
+    for (mode, ss) in runs.iter() {
+        let mut latencies = ss.sync_latencies.clone();
+        latencies.sort();
+        let max = latencies.last().cloned().unwrap_or(0);
+        println!(
+            "  {:<15} {:>12} {:>12} {:>12} {:>12}",
+            mode.name(),
+            format!(
+                "{:?}",
+                time::Duration::from_micros(stats::percentile(&latencies, 0.50))
+            ),
+            format!(
+                "{:?}",
+                time::Duration::from_micros(stats::percentile(&latencies, 0.99))
+            ),
+            format!(
+                "{:?}",
+                time::Duration::from_micros(stats::percentile(&latencies, 0.999))
+            ),
+            format!("{:?}", time::Duration::from_micros(max)),
+        );
+    }
+    if runs.iter().any(|(mode, _)| *mode == SyncMode::Flush) {
+        println!("  note: flush only empties userspace buffers, it is not durable");
     }
+}
 
-    fn path_throughput_plot(opt: &Opt, block_size: isize) -> path::PathBuf {
-        let mut p = path::PathBuf::new();
-        p.push(&opt.path);
-        p.push(format!(
-            "diskio-plot-throughput-{}Rx{}Wx{}x{}.png",
-            opt.nreaders(),
-            opt.nwriters(),
-            humanize(block_size.try_into().unwrap()),
-            opt.duration,
-        ));
-        p
+// committer syncs the data files written by writers.
+fn new_committer(opt: &Opt, interval: time::Duration) -> Committer {
+    let ids = if opt.pwrite { 1 } else { opt.writers };
+    let files = (0..ids)
+        .map(|id| {
+            let filename = Context::open_data_file(id, opt).unwrap();
+            fs::OpenOptions::new().write(true).open(filename).unwrap()
+        })
+        .collect();
+    Committer::new(files, interval)
+}
+
+fn sweep_record(opt: &Opt, row: &str, bsize: isize, ss: &Stats) {
+    if ss.count == 0 {
+        return;
+    }
+    // mean of per-interval values, per second.
+    let per_sec = |values: &[u64]| match values.len() {
+        0 => None,
+        n => {
+            let mean = (values.iter().sum::<u64>() as f64) / (n as f64);
+            Some(mean / ss.interval.as_secs_f64())
+        }
+    };
+    let mb_per_sec = per_sec(&ss.throughputs).map(|rate| rate / (1024_f64 * 1024_f64));
+    let iops = per_sec(&ss.ops);
+    let mut latencies = ss.sync_latencies.clone();
+    latencies.sort();
+    SWEEP.lock().unwrap().push(SweepCell {
+        row: row.to_string(),
+        threads: opt.swept_threads(),
+        block_size: bsize,
+        rate: match opt.throughput_unit() {
+            plot::Unit::Iops => iops,
+            plot::Unit::Bytes => mb_per_sec,
+        },
+        mb_per_sec,
+        iops,
+        ops: ss.count,
+        bytes: ss.bytes,
+        p50: stats::percentile(&latencies, 0.50),
+        p99: stats::percentile(&latencies, 0.99),
+        p999: stats::percentile(&latencies, 0.999),
+        max: latencies.last().cloned().unwrap_or(0),
+        cpu: ss.cpu,
+    });
+    if let Some(dir) = &opt.hdr_out {
+        save_hdr(opt, dir, row, bsize, &latencies)
+            .unwrap_or_else(|err| println!("unable to write hdr histogram to `{}`: {}", dir, err));
     }
 }
 
-static W_TOTAL: AtomicU64 = AtomicU64::new(0);
-static R_TOTAL: AtomicU64 = AtomicU64::new(0);
+// percentile distribution of a configuration, in milliseconds, and its
+// interval log.
+fn save_hdr(opt: &Opt, dir: &str, row: &str, bsize: isize, latencies: &[u64]) -> io::Result<()> {
+    let histogram = hdr::histogram(latencies);
+    fs::create_dir_all(dir)?;
+    let file = Context::path_hdr(opt, dir, row, bsize, "hgrm");
+    let mut fd = io::BufWriter::new(fs::File::create(file)?);
+    hdr::write_percentiles(&histogram, &mut fd, 1000_f64)?;
+    fd.flush()?;
 
-fn main() {
-    let opt = Opt::from_args();
+    let file = Context::path_hdr(opt, dir, row, bsize, "hlog");
+    let mut fd = io::BufWriter::new(fs::File::create(file)?);
+    let elapsed = time::Duration::from_secs(opt.duration);
+    hdr::write_log(&histogram, &mut fd, elapsed, &row.replace(' ', "-"))?;
+    fd.flush()
+}
 
-    for bsize in opt.clone().block_size.get_blocks() {
-        // io: append data
-        let mut threads = vec![];
-        let start_time = time::SystemTime::now();
-        let append_bsize = opt.append_block_size(bsize);
-        for i in 0..opt.nappenders() {
-            let ctxt = Context::new_append(i, append_bsize, opt.clone());
-            threads.push(thread::spawn(move || append_thread(i, ctxt)));
-        }
-        let ss = aggregate_threads(threads);
-        log_details(append_bsize, start_time, &ss);
-        do_plot(append_bsize, &opt, ss);
-        W_TOTAL.store(0, Ordering::Relaxed);
+// append every configuration recorded since `from` to the results db.
+fn save_results(opt: &Opt, environ: &Environ, file: &str, from: usize) {
+    let timestamp = chrono::Local::now().to_rfc3339();
+    let sweep = SWEEP.lock().unwrap();
+    let rows: Vec<results::Row> = sweep[from..]
+        .iter()
+        .map(|cell| {
+            results::Row::new()
+                .text("time", &timestamp)
+                .text("label", opt.label.as_deref().unwrap_or(""))
+                .text("path", &opt.path)
+                .text("phase", &cell.row)
+                .num("block_size", cell.block_size)
+                .num("appenders", opt.nappenders())
+                .num("writers", opt.writers)
+                .num("readers", opt.nreaders())
+                .num("duration", opt.duration)
+                .num("ops", cell.ops)
+                .num("bytes", cell.bytes)
+                .float("mb_per_sec", cell.mb_per_sec)
+                .float("iops", cell.iops)
+                .num("p50_us", cell.p50)
+                .num("p99_us", cell.p99)
+                .num("p999_us", cell.p999)
+                .num("max_us", cell.max)
+                .float("cpu_user_pct", cell.cpu.map(|c| c.user))
+                .float("cpu_system_pct", cell.cpu.map(|c| c.system))
+                .float("cpu_iowait_pct", cell.cpu.map(|c| c.iowait))
+                .raw("env", environ.to_json_line())
+        })
+        .collect();
+    if let Err(err) = results::append(file, &rows) {
+        println!("unable to append results to `{}`: {}", file, err);
+    }
+}
 
-        // io: other operations
-        let mut threads = vec![];
-        let start_time = time::SystemTime::now();
-        for i in 0..opt.writers {
-            let ctxt = Context::new_write(i, bsize, opt.clone());
-            threads.push(thread::spawn(move || writer_thread(i, ctxt)));
-        }
-        for i in 0..opt.rangers {
-            let ctxt = Context::new_read(i, bsize, opt.clone());
-            threads.push(thread::spawn(move || range_thread(i, ctxt)));
+// overview of the whole sweep, one chart for throughput and one for p99.
+fn do_plot_sweep(opt: &Opt) {
+    let sweep = SWEEP.lock().unwrap();
+    let mut rows: Vec<String> = vec![];
+    let mut bsizes: Vec<isize> = vec![];
+    for cell in sweep.iter() {
+        if !rows.contains(&cell.label()) {
+            rows.push(cell.label());
         }
-        for i in 0..opt.reverses {
-            let ctxt = Context::new_read(i, bsize, opt.clone());
-            threads.push(thread::spawn(move || reverse_thread(i, ctxt)));
+        if !bsizes.contains(&cell.block_size) {
+            bsizes.push(cell.block_size);
         }
-        for i in 0..opt.readers {
-            let ctxt = Context::new_read(i, bsize, opt.clone());
-            threads.push(thread::spawn(move || reader_thread(i, ctxt)));
-        }
-        let ss = aggregate_threads(threads);
-        log_details(bsize, start_time, &ss);
-        do_plot(bsize, &opt, ss);
-        W_TOTAL.store(0, Ordering::Relaxed);
-        R_TOTAL.store(0, Ordering::Relaxed);
+    }
+    if !opt.plotting() || bsizes.len() < 2 {
+        return;
+    }
+    bsizes.sort_unstable();
+    let cols: Vec<String> = bsizes
+        .iter()
+        .map(|b| humanize((*b).try_into().unwrap()))
+        .collect();
+    let matrix = |value: &dyn Fn(&SweepCell) -> Option<f64>| -> Vec<Vec<Option<f64>>> {
+        rows.iter()
+            .map(|row| {
+                bsizes
+                    .iter()
+                    .map(|b| {
+                        sweep
+                            .iter()
+                            .rfind(|c| &c.label() == row && c.block_size == *b)
+                            .and_then(value)
+                    })
+                    .collect()
+            })
+            .collect()
+    };
 
-        // remove files
-        (0..opt.nappenders()).for_each(|i| Context::drop_data_file(i, &opt));
+    plot::sweep_matrix(
+        Context::path_sweep(opt, "throughput"),
+        format!(
+            "throughput by block size, wr:{}, rd:{}",
+            opt.nwriters(),
+            opt.nreaders()
+        ),
+        &rows,
+        &cols,
+        &matrix(&|c| c.rate),
+        match opt.throughput_unit() {
+            plot::Unit::Iops => "IOPS",
+            plot::Unit::Bytes => "MB/sec",
+        },
+    )
+    .unwrap_or_else(|err| plot_failed("throughput sweep matrix", err));
 
-        println!("");
-    }
+    plot::sweep_matrix(
+        Context::path_sweep(opt, "p99"),
+        format!(
+            "p99 latency by block size, wr:{}, rd:{}",
+            opt.nwriters(),
+            opt.nreaders()
+        ),
+        &rows,
+        &cols,
+        &matrix(&|c| Some((c.p99 as f64) / 1000_f64)),
+        "p99 latency, millisecond",
+    )
+    .unwrap_or_else(|err| plot_failed("p99 sweep matrix", err));
 }
 
-fn aggregate_threads(
-    threads: Vec<thread::JoinHandle<Result<Stats, DiskioError>>>, // active
-) -> Stats {
-    let mut aggr_stats = Stats::new();
-    for (i, thread) in threads.into_iter().enumerate() {
-        match thread.join() {
-            Ok(res) => match res {
-                Ok(stat) => aggr_stats.join(stat),
-                Err(err) => println!("thread {} errored: {}", i, err),
-            },
-            Err(_) => println!("thread {} paniced", i),
+// throughput against thread count, a series per phase and block size,
+// from the latest run of each thread count.
+fn scaling_series(opt: &Opt) -> Vec<(String, Vec<(u64, f64)>)> {
+    let sweep = SWEEP.lock().unwrap();
+    let mut keys: Vec<(String, isize)> = vec![];
+    for cell in sweep.iter().filter(|c| c.threads.is_some()) {
+        let key = (cell.row.clone(), cell.block_size);
+        if !keys.contains(&key) {
+            keys.push(key);
         }
     }
-    aggr_stats
+    keys.into_iter()
+        .map(|(row, bsize)| {
+            let points = opt
+                .thread_counts()
+                .into_iter()
+                .filter_map(|n| {
+                    sweep
+                        .iter()
+                        .rfind(|c| c.row == row && c.block_size == bsize && c.threads == n)
+                        .and_then(|c| c.rate.map(|rate| (n.unwrap() as u64, rate)))
+                })
+                .collect();
+            let label = format!("{} {}", row, humanize(bsize.try_into().unwrap()));
+            (label, points)
+        })
+        .collect()
 }
 
-fn log_details(bsize: isize, start: time::SystemTime, _ss: &Stats) {
-    let elapsed = start.elapsed().expect("failed to compute elapsed");
-    let w_total: usize = W_TOTAL.load(Ordering::Relaxed).try_into().unwrap();
-    let r_total: usize = R_TOTAL.load(Ordering::Relaxed).try_into().unwrap();
-
-    if w_total > 0 {
-        println!(
-            "wrote {}, using {} blocks in {:?}",
-            humanize(w_total),
-            humanize(bsize.try_into().unwrap()),
-            elapsed,
-            // humanize(_ss.file_size.try_into().unwrap()),
-        );
+fn log_scaling(opt: &Opt) {
+    if opt.thread_counts().len() < 2 {
+        return;
     }
-    if r_total > 0 {
-        println!(
-            "readr {}, using {} blocks in {:?}",
-            humanize(r_total),
-            humanize(bsize.try_into().unwrap()),
-            elapsed,
-            // humanize(_ss.file_size.try_into().unwrap()),
-        );
+    let unit = match opt.throughput_unit() {
+        plot::Unit::Iops => "IOPS",
+        plot::Unit::Bytes => "MB/sec",
+    };
+    println!("throughput by thread count, {}:", unit);
+    for (label, points) in scaling_series(opt).into_iter() {
+        let points: Vec<String> = points
+            .into_iter()
+            .map(|(n, rate)| format!("{}:{:.2}", n, rate))
+            .collect();
+        println!("  {:<20} {}", label, points.join("  "));
     }
 }
 
-fn do_plot(bsize: isize, opt: &Opt, ss: Stats) {
-    if opt.plot {
-        plot::latency(
-            Context::path_latency_plot(&opt, bsize),
+fn do_plot_scaling(opt: &Opt) {
+    if !opt.plotting() || opt.thread_counts().len() < 2 {
+        return;
+    }
+    plot::throughput_scaling(
+        Context::path_sweep(opt, "threads"),
+        format!("throughput by thread count, {}", opt.path),
+        opt.throughput_unit(),
+        scaling_series(opt),
+    )
+    .unwrap_or_else(|err| plot_failed("thread scaling", err));
+}
+
+fn do_plot_compare(bsize: isize, opt: &Opt, runs: Vec<(SyncMode, Stats)>) {
+    if opt.plotting() && !runs.is_empty() {
+        let modes: Vec<&str> = runs.iter().map(|(mode, _)| mode.name()).collect();
+        let interval = runs[0].1.interval;
+        let mut latencies = vec![];
+        let mut throughputs = vec![];
+        for (mode, ss) in runs.into_iter() {
+            latencies.push((mode.name().to_string(), ss.sync_latencies));
+            throughputs.push(match opt.throughput_unit() {
+                plot::Unit::Iops => (mode.name().to_string(), ss.ops),
+                plot::Unit::Bytes => (mode.name().to_string(), ss.throughputs),
+            });
+        }
+
+        plot::latency_compare(
+            Context::path_plot(opt, "latency-compare", bsize),
             format!(
-                "fd.sync_all() latency, block-size:{}, wr:{}, rd:{}",
+                "write latency with {}, block-size:{}",
+                modes.join(" vs "),
                 humanize(bsize.try_into().unwrap()),
-                opt.nwriters(),
-                opt.nreaders(),
             ),
-            ss.sync_latencies,
+            latencies,
         )
-        .expect("unable to plot latency");
+        .unwrap_or_else(|err| plot_failed("latency comparison", err));
 
-        plot::throughput(
-            Context::path_throughput_plot(&opt, bsize),
+        plot::throughput_compare(
+            Context::path_plot(opt, "throughput-compare", bsize),
             format!(
-                "throughput for block-size:{}, wr:{}, rd:{}",
+                "throughput with {}, block-size:{}",
+                modes.join(" vs "),
                 humanize(bsize.try_into().unwrap()),
-                opt.nwriters(),
-                opt.nreaders(),
             ),
-            ss.throughputs,
+            interval,
+            opt.throughput_unit(),
+            throughputs,
         )
-        .expect("unable to plot latency");
+        .unwrap_or_else(|err| plot_failed("throughput comparison", err));
     }
 }
 
-fn append_thread(_id: isize, mut ctxt: Context) -> Result<Stats, DiskioError> {
+fn append_thread(id: isize, mut ctxt: Context) -> Result<Stats, DiskioError> {
     // println!("append_thread {}", _id);
-    let mut ss = Stats::new();
+    let mut ss = Stats::new(ctxt.opt.sample_interval);
+    ss.seed(ctxt.opt.seed.wrapping_add(id as u128));
+    ss.prepare = ctxt.prepared;
     let block_size: isize = ctxt.block.len().try_into().unwrap();
+    let retries = ctxt.opt.retries;
+    let mut fpos = ctxt.fd.metadata()?.len();
     let start_time = time::SystemTime::now();
-    while start_time.elapsed().unwrap() < ctxt.duration {
+    while !stopped() && start_time.elapsed().unwrap() < ctxt.duration {
+        ss.refill += ctxt.prepare_block(ss.count);
+        // buffer preparation is timed apart, --reopen is part of the op.
         let lbegin = time::SystemTime::now();
-        match ctxt.fd.write(ctxt.block.as_slice())? {
-            n if n != ctxt.block.len() => {
-                let msg = format!("partial write {}", n);
-                Err(DiskioError(msg))
-            }
+        if ctxt.opt.reopen {
+            ctxt.reopen()?;
+        }
+        let probe = ctxt.opt.profile_probe(ss.count as usize);
+        match retry(retries, &mut ss.retries, || ctxt.write_block())? {
+            n if n != ctxt.block.len() => Err(DiskioError::PartialWrite {
+                wrote: n,
+                expected: ctxt.block.len(),
+            }),
             _ => Ok(()),
         }?;
-        ctxt.fd.sync_all()?;
+        let wrote = probe.map(|t| t.elapsed());
+        if ctxt.sync != SyncMode::None {
+            retry(retries, &mut ss.retries, || ctxt.sync_block(fpos))?;
+            ss.syncs += 1;
+        }
+        fpos += ctxt.block.len() as u64;
+        if let (Some(t), Some(wrote)) = (probe, wrote) {
+            ss.profile
+                .add(time::Duration::default(), wrote, t.elapsed() - wrote);
+        }
         W_TOTAL.fetch_add(block_size.try_into().unwrap(), Ordering::Relaxed);
         ss.click(lbegin, ctxt.block.len().try_into().unwrap())?;
     }
 
-    ss.file_size = fs::metadata(ctxt.filename)?.len();
+    ss.file_size = ctxt.fd.metadata()?.len();
+    Ok(ss)
+}
+
+// keep a write, linked to an fsync, in flight on every chain's file, all
+// from this one thread through io_uring. Latency of a chain's operation
+// runs from submitting its write until its fsync completes.
+#[cfg(target_os = "linux")]
+fn async_thread(chains: Vec<Context>) -> Result<Stats, DiskioError> {
+    use std::os::unix::io::AsRawFd;
+
+    let n = chains.len();
+    let (duration, interval) = (chains[0].duration, chains[0].opt.sample_interval);
+    let iovs: Vec<libc::iovec> = chains
+        .iter()
+        .map(|c| libc::iovec {
+            iov_base: c.block.as_ptr() as *mut libc::c_void,
+            iov_len: c.block.len(),
+        })
+        .collect();
+    let mut ring = uring::Ring::new((n * 2).next_power_of_two() as u32)?;
+
+    let mut css: Vec<Stats> = (0..n)
+        .map(|i| {
+            let mut cs = Stats::new(interval);
+            cs.seed(chains[i].opt.seed.wrapping_add(i as u128));
+            cs
+        })
+        .collect();
+    let mut begins = vec![time::SystemTime::now(); n];
+    let mut offsets = vec![0_u64; n];
+    let mut wrote = vec![false; n];
+    // chain buffers must outlive their in-flight entries, on error stop
+    // resubmitting and drain every chain before returning.
+    let mut failed: Option<DiskioError> = None;
+    let (start_time, mut inflight) = (time::SystemTime::now(), 0);
+    for (i, chain) in chains.iter().enumerate() {
+        // Safety: `iovs` and `chains` outlive the loop below, which
+        // reaps every chain.
+        match unsafe { ring.write_fsync(chain.fd.as_raw_fd(), &iovs[i], 0, (i * 2) as u64) } {
+            Ok(()) => inflight += 1,
+            Err(err) => {
+                failed = Some(err.into());
+                break;
+            }
+        }
+    }
+    while inflight > 0 {
+        let completions = match ring.submit_and_wait() {
+            Ok(completions) => completions,
+            Err(err) => {
+                // entries may still be in flight, leak their buffers.
+                std::mem::forget(chains);
+                return Err(err.into());
+            }
+        };
+        for (user_data, res) in completions {
+            let (i, synced) = ((user_data / 2) as usize, user_data % 2 == 1);
+            let len = chains[i].block.len();
+            let res = match (res, synced) {
+                (res, _) if res < 0 => Err(io::Error::from_raw_os_error(-res).into()),
+                (res, false) if (res as usize) != len => Err(DiskioError::PartialWrite {
+                    wrote: res as usize,
+                    expected: len,
+                }),
+                _ => Ok(()),
+            };
+            match (res, synced) {
+                (Err(err), _) => {
+                    failed = failed.or(Some(err));
+                    wrote[i] = false;
+                }
+                (Ok(()), false) => wrote[i] = true,
+                (Ok(()), true) if wrote[i] => {
+                    W_TOTAL.fetch_add(len as u64, Ordering::Relaxed);
+                    css[i].syncs += 1;
+                    if let Err(err) = css[i].click(begins[i], len as u64) {
+                        failed = failed.or(Some(err));
+                    }
+                    offsets[i] += len as u64;
+                }
+                (Ok(()), true) => (),
+            }
+            if !synced {
+                continue;
+            }
+            inflight -= 1;
+            let due = start_time.elapsed().map(|e| e < duration).unwrap_or(false);
+            if failed.is_none() && !stopped() && due {
+                begins[i] = time::SystemTime::now();
+                let fd = chains[i].fd.as_raw_fd();
+                // Safety: as above.
+                match unsafe { ring.write_fsync(fd, &iovs[i], offsets[i], (i * 2) as u64) } {
+                    Ok(()) => inflight += 1,
+                    Err(err) => failed = Some(err.into()),
+                }
+            }
+        }
+    }
+    if let Some(err) = failed {
+        return Err(err);
+    }
+
+    let mut ss = Stats::new(interval);
+    for (chain, mut cs) in chains.iter().zip(css) {
+        cs.file_size = chain.fd.metadata()?.len();
+        ss.join(cs.clone());
+        ss.threads.push(cs);
+    }
+    Ok(ss)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn async_thread(_chains: Vec<Context>) -> Result<Stats, DiskioError> {
+    let msg = "--async-files needs io_uring, supported only on linux";
+    Err(DiskioError::Config(msg.to_string()))
+}
+
+fn fsync_thread(id: isize, mut ctxt: Context, dirty: isize) -> Result<Stats, DiskioError> {
+    let mut ss = Stats::new(ctxt.opt.sample_interval);
+    ss.seed(ctxt.opt.seed.wrapping_add(id as u128));
+    ss.prepare = ctxt.prepared;
+    let dirty: usize = dirty.try_into().unwrap();
+    let start_time = time::SystemTime::now();
+    while !stopped() && start_time.elapsed().unwrap() < ctxt.duration {
+        let mut n = 0;
+        while n < dirty {
+            let m = (dirty - n).min(ctxt.block.len());
+            ctxt.fd.write_all(&ctxt.block[..m])?;
+            n += m;
+        }
+        W_TOTAL.fetch_add(dirty.try_into().unwrap(), Ordering::Relaxed);
+
+        let lbegin = time::SystemTime::now();
+        ctxt.fd.sync_all()?;
+        ss.syncs += 1;
+        ss.click(lbegin, dirty.try_into().unwrap())?;
+    }
+
+    ss.file_size = ctxt.fd.metadata()?.len();
+    Ok(ss)
+}
+
+fn files_thread(id: isize, bsize: isize, count: usize, opt: Opt) -> Result<Stats, DiskioError> {
+    let seed = opt.seed + (id as u128);
+    let mut rng = SmallRng::from_seed(seed.to_le_bytes());
+
+    let mut ss = Stats::new(opt.sample_interval);
+    ss.seed(seed);
+    let dist = opt.file_size_dist.clone();
+    let max_size = dist.as_ref().map(|d| d.max()).unwrap_or(bsize);
+    let buffer = vec![opt.fill_byte; max_size as usize];
+    let (mut k, mut total) = (0, 0);
+    while !stopped() && k < count {
+        let file = Context::many_file(id, k, &opt);
+        if !opt.tmpfile {
+            fs::remove_file(&file).ok();
+        }
+        let block = match &dist {
+            Some(dist) => &buffer[..dist.sample(&mut rng) as usize],
+            None => &buffer[..],
+        };
+
+        let lbegin = time::SystemTime::now();
+        let mut oo = fs::OpenOptions::new();
+        let mut fd = match opt.tmpfile {
+            true => {
+                tmpfile(oo.write(true)).map_err(DiskioError::Config)?;
+                oo.open(&opt.path)?
+            }
+            false => oo.write(true).create_new(true).open(&file)?,
+        };
+        match fd.write(block)? {
+            n if n != block.len() => Err(DiskioError::PartialWrite {
+                wrote: n,
+                expected: block.len(),
+            }),
+            _ => Ok(()),
+        }?;
+        fd.sync_all()?;
+        ss.syncs += 1;
+        W_TOTAL.fetch_add(block.len().try_into().unwrap(), Ordering::Relaxed);
+        ss.click(lbegin, block.len().try_into().unwrap())?;
+        if dist.is_some() {
+            ss.sample_size(block.len() as u64);
+        }
+        total += block.len() as u64;
+        k += 1;
+    }
+
+    // anonymous files are already gone, with --tmpfile.
+    for k in (0..k).filter(|_| !opt.tmpfile) {
+        fs::remove_file(Context::many_file(id, k, &opt)).ok();
+    }
+    ss.file_size = total;
     Ok(ss)
 }
 
 fn writer_thread(id: isize, mut ctxt: Context) -> Result<Stats, DiskioError> {
     // println!("writer_thread {}", id);
+    let seed = ctxt.opt.seed.wrapping_add(id as u128);
+    let mut rng = SmallRng::from_seed(seed.to_le_bytes());
+
+    let mut ss = Stats::new(ctxt.opt.sample_interval);
+    ss.seed(seed);
+    ss.prepare = ctxt.prepared;
+    let file_size = ctxt.fd.metadata()?.len();
+    let block_size: u64 = ctxt
+        .block
+        .len()
+        .try_into()
+        .map_err(|_| DiskioError::Overflow("block size"))?;
+    let align = ctxt.opt.offset_align(ctxt.block.len());
+    let retries = ctxt.opt.retries;
+    // with --pwrite, each writer owns a non-overlapping region of the file.
+    // marked blocks follow the data file header.
+    let (header, file_size) = match ctxt.opt.durability_markers {
+        true => {
+            let header = verify::HEADER_SIZE as u64;
+            (header, file_size.saturating_sub(header))
+        }
+        false => (0, file_size),
+    };
+    let (base, span) = match ctxt.opt.pwrite {
+        true => {
+            let span = (file_size / (ctxt.opt.writers as u64) / align) * align;
+            (
+                header.saturating_add((id as u64).saturating_mul(span)),
+                span,
+            )
+        }
+        false => (header, file_size),
+    };
+    let observer = Arc::clone(&ctxt.observer);
+    let mut injector = Injector::new(seed, ctxt.opt.inject_latency, ctxt.opt.inject_error);
+    let start_time = time::SystemTime::now();
+    while !stopped() && start_time.elapsed().unwrap() < ctxt.duration {
+        let scheduled = ctxt.throttle.as_ref().and_then(|t| t.wait());
+        let fpos = {
+            let scale: f64 = rng.gen_range(0.0, 1.0);
+            let fpos = ((span as f64) * scale) as u64;
+            base + (fpos / align) * align
+        };
+        // decided before the io, an injected error fails the writer like
+        // an io error would, operations done before it are accounted.
+        if injector.error() {
+            return Err(DiskioError::Injected(ss.count));
+        }
+        // with --reopen, open and close are timed as part of the operation.
+        let reopened = match ctxt.opt.reopen {
+            true => {
+                let begin = time::SystemTime::now();
+                ctxt.reopen()?;
+                Some(begin)
+            }
+            false => None,
+        };
+        let probe = ctxt.opt.profile_probe(ss.count as usize);
+        if !ctxt.opt.pwrite {
+            ctxt.fd.seek(io::SeekFrom::Start(fpos))?;
+        }
+        let seeked = probe.map(|t| t.elapsed());
+        ss.refill += ctxt.prepare_block(ss.count);
+
+        let lbegin = scheduled.or(reopened).unwrap_or_else(time::SystemTime::now);
+        observer.before_write(id, fpos, ctxt.block.len());
+        let began = time::Instant::now();
+        let n = match ctxt.opt.pwrite {
+            true => retry(retries, &mut ss.retries, || ctxt.write_block_at(fpos))?,
+            false => retry(retries, &mut ss.retries, || ctxt.write_block())?,
+        };
+        observer.after_write(id, fpos, n, began.elapsed());
+        match n {
+            n if n != ctxt.block.len() => Err(DiskioError::PartialWrite {
+                wrote: n,
+                expected: ctxt.block.len(),
+            }),
+            _ => Ok(()),
+        }?;
+        let wrote = probe.map(|t| t.elapsed());
+        if ctxt.sync != SyncMode::None {
+            observer.before_sync(id, fpos);
+            let began = time::Instant::now();
+            retry(retries, &mut ss.retries, || ctxt.sync_block(fpos))?;
+            observer.after_sync(id, fpos, began.elapsed());
+            ss.syncs += 1;
+        }
+        if let Some(committer) = &ctxt.commit {
+            committer.wait();
+        }
+        if let (Some(t), Some(seeked), Some(wrote)) = (probe, seeked, wrote) {
+            ss.profile.add(seeked, wrote - seeked, t.elapsed() - wrote);
+        }
+        if let Some(delay) = injector.latency() {
+            thread::sleep(delay);
+        }
+        W_TOTAL.fetch_add(block_size, Ordering::Relaxed);
+        ss.click(lbegin, block_size)?;
+        if ctxt.opt.plotting() {
+            ss.sample_offset(fpos);
+        }
+    }
+
+    ss.file_size = fs::metadata(ctxt.filename)?.len();
+    Ok(ss)
+}
+
+// random aligned reads and writes, `read_pct` percent of them reads.
+fn mixed_thread(id: isize, mut ctxt: Context, read_pct: u8) -> Result<Stats, DiskioError> {
     let seed = ctxt.opt.seed + (id as u128);
     let mut rng = SmallRng::from_seed(seed.to_le_bytes());
 
-    let mut ss = Stats::new();
+    let mut ss = Stats::new(ctxt.opt.sample_interval);
+    ss.seed(seed);
+    ss.prepare = ctxt.prepared;
     let file_size = ctxt.fd.metadata()?.len();
-    let block_size: isize = ctxt.block.len().try_into().unwrap();
+    let align = ctxt.opt.offset_align(ctxt.block.len());
+    let retries = ctxt.opt.retries;
     let start_time = time::SystemTime::now();
-    while start_time.elapsed().unwrap() < ctxt.duration {
+    while !stopped() && start_time.elapsed().unwrap() < ctxt.duration {
         let fpos = {
             let scale: f64 = rng.gen_range(0.0, 1.0);
-            ((file_size as f64) * scale) as u64
+            let fpos = ((file_size as f64) * scale) as u64;
+            (fpos / align) * align
         };
+        let read = rng.gen_range(0, 100) < read_pct;
         ctxt.fd.seek(io::SeekFrom::Start(fpos))?;
 
         let lbegin = time::SystemTime::now();
-        match ctxt.fd.write(ctxt.block.as_slice())? {
-            n if n != ctxt.block.len() => {
-                let msg = format!("partial write {}", n);
-                Err(DiskioError(msg))
+        if read {
+            let n = retry(retries, &mut ss.retries, || ctxt.fd.read(&mut ctxt.block))?;
+            let n: u64 = n.try_into().unwrap();
+            R_TOTAL.fetch_add(n, Ordering::Relaxed);
+            if let Some(latency) = ss.click(lbegin, n)? {
+                ss.read_latencies.push(latency);
             }
-            _ => Ok(()),
-        }?;
-        ctxt.fd.sync_all()?;
-        W_TOTAL.fetch_add(block_size.try_into().unwrap(), Ordering::Relaxed);
-        ss.click(lbegin, ctxt.block.len().try_into().unwrap())?;
+        } else {
+            match retry(retries, &mut ss.retries, || ctxt.write_block())? {
+                n if n != ctxt.block.len() => Err(DiskioError::PartialWrite {
+                    wrote: n,
+                    expected: ctxt.block.len(),
+                }),
+                _ => Ok(()),
+            }?;
+            if ctxt.sync != SyncMode::None {
+                retry(retries, &mut ss.retries, || ctxt.sync_block(fpos))?;
+                ss.syncs += 1;
+            }
+            if let Some(committer) = &ctxt.commit {
+                committer.wait();
+            }
+            let n: u64 = ctxt.block.len().try_into().unwrap();
+            W_TOTAL.fetch_add(n, Ordering::Relaxed);
+            if let Some(latency) = ss.click(lbegin, n)? {
+                ss.write_latencies.push(latency);
+            }
+        }
+        if ctxt.opt.plotting() {
+            ss.sample_offset(fpos);
+        }
     }
 
     ss.file_size = fs::metadata(ctxt.filename)?.len();
     Ok(ss)
 }
 
-fn range_thread(_id: isize, mut ctxt: Context) -> Result<Stats, DiskioError> {
-    let mut ss = Stats::new();
+fn range_thread(id: isize, mut ctxt: Context) -> Result<Stats, DiskioError> {
+    let mut ss = Stats::new(ctxt.opt.sample_interval);
+    ss.seed(ctxt.opt.seed.wrapping_add(id as u128));
+    ss.prepare = ctxt.prepared;
     let (mut fpos, file_size) = (0, ctxt.fd.metadata()?.len());
     let n: u64 = ctxt.block.len().try_into().unwrap();
+    let retries = ctxt.opt.retries;
     let start_time = time::SystemTime::now();
-    while start_time.elapsed().unwrap() < ctxt.duration {
+    while !stopped() && start_time.elapsed().unwrap() < ctxt.duration {
         fpos = (fpos + n) % file_size;
         ctxt.fd.seek(io::SeekFrom::Start(fpos))?;
 
         let lbegin = time::SystemTime::now();
-        let n: u64 = ctxt.fd.read(ctxt.block.as_mut_slice())?.try_into().unwrap();
+        let n = retry(retries, &mut ss.retries, || ctxt.fd.read(&mut ctxt.block))?;
+        let n: u64 = n.try_into().unwrap();
         R_TOTAL.fetch_add(n, Ordering::Relaxed);
-        ss.click(lbegin, n.try_into().unwrap())?;
+        ss.click(lbegin, n)?;
+        if ctxt.opt.plotting() {
+            ss.sample_offset(fpos);
+        }
     }
 
     ss.file_size = fs::metadata(ctxt.filename)?.len();
     Ok(ss)
 }
 
-fn reverse_thread(_id: isize, mut ctxt: Context) -> Result<Stats, DiskioError> {
-    let mut ss = Stats::new();
+fn reverse_thread(id: isize, mut ctxt: Context) -> Result<Stats, DiskioError> {
+    let mut ss = Stats::new(ctxt.opt.sample_interval);
+    ss.seed(ctxt.opt.seed.wrapping_add(id as u128));
+    ss.prepare = ctxt.prepared;
     let file_size = ctxt.fd.metadata()?.len();
     let n: u64 = ctxt.block.len().try_into().unwrap();
     let mut fpos = file_size - n;
+    let retries = ctxt.opt.retries;
     let start_time = time::SystemTime::now();
-    while start_time.elapsed().unwrap() < ctxt.duration {
+    while !stopped() && start_time.elapsed().unwrap() < ctxt.duration {
         fpos = (fpos - n) % file_size;
         ctxt.fd.seek(io::SeekFrom::Start(fpos))?;
 
         let lbegin = time::SystemTime::now();
-        let n: u64 = ctxt.fd.read(ctxt.block.as_mut_slice())?.try_into().unwrap();
+        let n = retry(retries, &mut ss.retries, || ctxt.fd.read(&mut ctxt.block))?;
+        let n: u64 = n.try_into().unwrap();
         R_TOTAL.fetch_add(n, Ordering::Relaxed);
-        ss.click(lbegin, n.try_into().unwrap())?;
+        ss.click(lbegin, n)?;
+        if ctxt.opt.plotting() {
+            ss.sample_offset(fpos);
+        }
     }
 
     ss.file_size = fs::metadata(ctxt.filename)?.len();
@@ -432,26 +3560,116 @@ fn reader_thread(id: isize, mut ctxt: Context) -> Result<Stats, DiskioError> {
     let seed = ctxt.opt.seed + (id as u128);
     let mut rng = SmallRng::from_seed(seed.to_le_bytes());
 
-    let mut ss = Stats::new();
+    let mut ss = Stats::new(ctxt.opt.sample_interval);
+    ss.seed(seed);
+    ss.prepare = ctxt.prepared;
     let file_size = ctxt.fd.metadata()?.len();
+    let align = ctxt.opt.offset_align(ctxt.block.len());
+    let retries = ctxt.opt.retries;
     let start_time = time::SystemTime::now();
-    while start_time.elapsed().unwrap() < ctxt.duration {
+    while !stopped() && start_time.elapsed().unwrap() < ctxt.duration {
         let fpos = {
             let scale: f64 = rng.gen_range(0.0, 1.0);
-            ((file_size as f64) * scale) as u64
+            let fpos = ((file_size as f64) * scale) as u64;
+            (fpos / align) * align
         };
         ctxt.fd.seek(io::SeekFrom::Start(fpos))?;
 
         let lbegin = time::SystemTime::now();
-        let n: u64 = ctxt.fd.read(ctxt.block.as_mut_slice())?.try_into().unwrap();
+        let n = retry(retries, &mut ss.retries, || ctxt.fd.read(&mut ctxt.block))?;
+        let n: u64 = n.try_into().unwrap();
         R_TOTAL.fetch_add(n, Ordering::Relaxed);
-        ss.click(lbegin, n.try_into().unwrap())?;
+        ss.click(lbegin, n)?;
+        if ctxt.opt.plotting() {
+            ss.sample_offset(fpos);
+        }
     }
 
     ss.file_size = fs::metadata(ctxt.filename)?.len();
     Ok(ss)
 }
 
+// Retry `op` on transient io errors, that network filesystems return now
+// and then, upto `retries` times with exponential backoff. Every retry is
+// counted in `count`. Other errors fail right away.
+fn retry<T, F>(retries: usize, count: &mut u64, mut op: F) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    let mut backoff = time::Duration::from_millis(1);
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(ref err) if attempt < retries && is_transient(err) => {
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(time::Duration::from_secs(1));
+                attempt += 1;
+                *count += 1;
+            }
+            res => break res,
+        }
+    }
+}
+
+fn is_transient(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => true,
+        #[cfg(unix)]
+        _ => err.raw_os_error() == Some(libc::ESTALE),
+        #[cfg(not(unix))]
+        _ => false,
+    }
+}
+
+// parse durations like `90`, `500ms`, `90s`, `15m`, `2h`, plain numbers
+// are seconds.
+fn parse_duration(s: &str) -> Result<time::Duration, DiskioError> {
+    let (digits, millis) = if let Some(digits) = s.strip_suffix("ms") {
+        (digits, 1)
+    } else if let Some(digits) = s.strip_suffix('s') {
+        (digits, 1000)
+    } else if let Some(digits) = s.strip_suffix('m') {
+        (digits, 60 * 1000)
+    } else if let Some(digits) = s.strip_suffix('h') {
+        (digits, 3600 * 1000)
+    } else {
+        (s, 1000)
+    };
+    match digits.parse::<u64>() {
+        Ok(n) => Ok(time::Duration::from_millis(n * millis)),
+        Err(err) => {
+            let msg = format!("invalid duration {:?}: {}", s, err);
+            Err(DiskioError::Config(msg))
+        }
+    }
+}
+
+// percentage between 0 and 100.
+fn parse_percent(s: &str) -> Result<u8, DiskioError> {
+    match s.parse::<u8>() {
+        Ok(pct) if pct <= 100 => Ok(pct),
+        Ok(_) => {
+            let msg = format!("percentage {:?} not within 0..100", s);
+            Err(DiskioError::Config(msg))
+        }
+        Err(err) => {
+            let msg = format!("invalid percentage {:?}: {}", s, err);
+            Err(DiskioError::Config(msg))
+        }
+    }
+}
+
+fn parse_hex_byte(s: &str) -> Result<u8, DiskioError> {
+    let digits = s.trim_start_matches("0x").trim_start_matches("0X");
+    match u8::from_str_radix(digits, 16) {
+        Ok(byte) => Ok(byte),
+        Err(err) => {
+            let msg = format!("invalid fill byte {:?}: {}", s, err);
+            Err(DiskioError::Config(msg))
+        }
+    }
+}
+
 fn humanize(bytes: usize) -> String {
     if bytes < 1024 {
         format!("{}B", bytes)
@@ -466,22 +3684,32 @@ fn humanize(bytes: usize) -> String {
     }
 }
 
+// Size arguments are either a list, `512` or `4K,64K,1M`, or a range,
+// `4K..1M`, that expands to the BLOCK_SIZES falling within it. A single
+// value is a list of one, the two forms never overlap. A stepped range,
+// `5000:20000:5000`, is taken literally and parsed into a list.
 #[derive(Debug, Clone)]
 enum SizeArg {
-    None,
-    Range(Option<isize>, Option<isize>),
+    Range(isize, isize),
     List(Vec<isize>),
 }
 
+// bound on the sizes a stepped range expands to.
+const MAX_STEPS: isize = 1024;
+
 lazy_static! {
     static ref ARG_RE1: Regex = {
-        let patt = r"^([0-9]+[kKmMgG]?)(\.\.[0-9]+[kKmMgG]?)?$";
+        let patt = r"^([0-9]+[kKmMgG]?)\.\.([0-9]+[kKmMgG]?)$";
         Regex::new(patt).unwrap()
     };
     static ref ARG_RE2: Regex = {
         let patt = r"^([0-9]+[kKmMgG]?)(,[0-9]+[kKmMgG]?)*$";
         Regex::new(patt).unwrap()
     };
+    static ref ARG_RE3: Regex = {
+        let patt = r"^([0-9]+[kKmMgG]?):([0-9]+[kKmMgG]?):([0-9]+[kKmMgG]?)$";
+        Regex::new(patt).unwrap()
+    };
     static ref BLOCK_SIZES: [isize; 9] = [
         128,
         256,
@@ -494,7 +3722,7 @@ lazy_static! {
         100 * 1024 * 1024,
     ];
     static ref DATA_SIZES: [isize; 6] = [
-        1 * 1024 * 1024,
+        1024 * 1024,
         10 * 1024 * 1024,
         100 * 1024 * 1024,
         1024 * 1024 * 1024,
@@ -507,38 +3735,33 @@ impl FromStr for SizeArg {
     type Err = String;
 
     fn from_str(s: &str) -> Result<SizeArg, Self::Err> {
-        //println!("re1 {}", s);
-        match ARG_RE1.captures(s) {
-            None => (),
-            Some(captrs) => {
-                let x = captrs.get(1).map(|m| SizeArg::to_isize(m.as_str()));
-                let y = captrs.get(2).map(|m| {
-                    let s = m.as_str().chars().skip(2).collect::<String>();
-                    SizeArg::to_isize(s.as_str())
-                });
-                // println!("re1 {}, {:?} {:?}", s, x, y);
-                return Ok(SizeArg::Range(x.transpose()?, y.transpose()?));
-            }
-        };
-        //println!("re2 {}", s);
-        match ARG_RE2.captures(s) {
-            None => Ok(SizeArg::None),
-            Some(captrs) => {
-                let sizes = captrs
-                    .get(0)
-                    .unwrap()
-                    .as_str()
-                    .split(',')
-                    .map(|s| SizeArg::to_isize(s).unwrap())
-                    .collect::<Vec<isize>>();
-                return Ok(SizeArg::List(sizes));
-            }
+        if let Some(captrs) = ARG_RE1.captures(s) {
+            let x = SizeArg::to_isize(&captrs[1])?;
+            let y = SizeArg::to_isize(&captrs[2])?;
+            return Ok(SizeArg::Range(x, y));
+        }
+        if ARG_RE2.is_match(s) {
+            let sizes: Result<Vec<isize>, String> = s.split(',').map(SizeArg::to_isize).collect();
+            return Ok(SizeArg::List(sizes?));
         }
+        if let Some(captrs) = ARG_RE3.captures(s) {
+            let x = SizeArg::to_isize(&captrs[1])?;
+            let y = SizeArg::to_isize(&captrs[2])?;
+            let step = SizeArg::to_isize(&captrs[3])?;
+            return SizeArg::stepped(x, y, step).map(SizeArg::List);
+        }
+        Err(format!(
+            "invalid size `{}`, expected `n`, `n,m,..`, `n..m` or `n:m:step`",
+            s
+        ))
     }
 }
 
 impl SizeArg {
     fn to_isize(s: &str) -> Result<isize, String> {
+        if s.is_empty() {
+            return Err("empty size".to_string());
+        }
         let chs: Vec<char> = s.chars().collect();
         let (s, amp) = match chs[chs.len() - 1] {
             'k' | 'K' => {
@@ -565,25 +3788,130 @@ impl SizeArg {
         // println!("{}", s);
         match s.parse::<isize>() {
             Err(err) => Err(format!("parse: {:?}", err)),
-            Ok(n) => Ok(n * amp),
+            Ok(n) => n
+                .checked_mul(amp)
+                .ok_or_else(|| format!("size `{}` overflows", s)),
         }
     }
 
+    // size of at least one byte.
+    fn to_positive(s: &str) -> Result<isize, String> {
+        match SizeArg::to_isize(s)? {
+            n if n > 0 => Ok(n),
+            _ => Err(format!("size `{}` must be positive", s)),
+        }
+    }
+
+    // every size from `from` up to `till`, `step` apart.
+    fn stepped(from: isize, till: isize, step: isize) -> Result<Vec<isize>, String> {
+        if from < 1 || step < 1 || till < from {
+            let msg = format!("invalid range `{}:{}:{}`", from, till, step);
+            return Err(msg);
+        } else if (till - from) / step >= MAX_STEPS {
+            let msg = format!("range `{}:{}:{}` has too many steps", from, till, step);
+            return Err(msg);
+        }
+        let sizes = std::iter::successors(Some(from), |n| Some(n + step));
+        Ok(sizes.take_while(|n| *n <= till).collect())
+    }
+
+    // thread counts, `n..m` doubles from n up to m.
+    fn get_counts(self) -> Vec<isize> {
+        let (from, till) = match self {
+            SizeArg::List(counts) => return counts,
+            SizeArg::Range(x, y) => (x.max(1), y),
+        };
+        let mut counts: Vec<isize> = std::iter::successors(Some(from), |n| Some(n * 2))
+            .take_while(|n| *n < till)
+            .collect();
+        counts.push(till.max(from));
+        counts
+    }
+
     fn get_blocks(self) -> Vec<isize> {
         let (from, till) = match self {
-            SizeArg::None => return vec![],
             SizeArg::List(sizes) => return sizes,
-            SizeArg::Range(None, None) => return vec![],
-            SizeArg::Range(Some(x), None) => return vec![x],
-            SizeArg::Range(None, Some(_)) => unreachable!(),
-            SizeArg::Range(Some(x), Some(y)) => (x, y),
+            SizeArg::Range(x, y) => (x, y),
         };
         BLOCK_SIZES
             .clone()
             .iter()
             .skip_while(|x| **x < from)
             .take_while(|x| **x <= till)
-            .map(|x| *x)
+            .copied()
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks(s: &str) -> Vec<isize> {
+        s.parse::<SizeArg>().unwrap().get_blocks()
+    }
+
+    #[test]
+    fn test_size_arg_single() {
+        assert_eq!(blocks("4K"), vec![4096]);
+        assert_eq!(blocks("512"), vec![512]);
+    }
+
+    #[test]
+    fn test_size_arg_list() {
+        assert_eq!(blocks("4K,64K,1M"), vec![4096, 65536, 1048576]);
+    }
+
+    #[test]
+    fn test_size_arg_range() {
+        let ladder = vec![10 * 1024, 100 * 1024, 1024 * 1024];
+        assert_eq!(blocks("4K..1M"), ladder);
+        assert_eq!(blocks("5000..20000"), vec![10 * 1024]);
+    }
+
+    #[test]
+    fn test_size_arg_stepped() {
+        assert_eq!(blocks("5000:20000:5000"), vec![5000, 10000, 15000, 20000]);
+        assert_eq!(blocks("4K:10K:4K"), vec![4096, 8192]);
+    }
+
+    #[test]
+    fn test_size_arg_rejected() {
+        let rejected = [
+            "",
+            "abc",
+            "4X",
+            "4K..",
+            "..1M",
+            "4K,,1M",
+            "4K;64K",
+            "1:2",
+            "0:4096:1024",
+            "4096:0:1024",
+            "4096:8192:0",
+            "1:100000:1",
+        ];
+        for s in rejected.iter() {
+            assert!(s.parse::<SizeArg>().is_err(), "{:?} accepted", s);
+        }
+    }
+
+    #[test]
+    fn test_size_arg_positive() {
+        assert_eq!(SizeArg::to_positive("1").unwrap(), 1);
+        assert_eq!(SizeArg::to_positive("4K").unwrap(), 4096);
+        for s in ["", "0", "-4K", "K", "4X"].iter() {
+            assert!(SizeArg::to_positive(s).is_err(), "{:?} accepted", s);
+        }
+    }
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("0").unwrap(), 0);
+        assert_eq!(parse_percent("70").unwrap(), 70);
+        assert_eq!(parse_percent("100").unwrap(), 100);
+        for s in ["101", "255", "-1", "abc", ""].iter() {
+            assert!(parse_percent(s).is_err(), "{:?} accepted", s);
+        }
+    }
+}
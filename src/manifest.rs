@@ -0,0 +1,71 @@
+use std::{fs, io, path, sync::Mutex};
+
+use crate::environ::Environ;
+use crate::results::quote;
+
+// Version of the manifest layout.
+const MANIFEST_VERSION: u32 = 1;
+
+// A file written into the output directory, along with the
+// configuration that produced it.
+pub struct Artifact {
+    pub file: path::PathBuf,
+    pub kind: &'static str,
+    pub block_size: Option<isize>,
+    pub readers: isize,
+    pub writers: isize,
+    pub duration: u64,
+}
+
+impl Artifact {
+    fn to_json_line(&self, dir: &path::Path) -> String {
+        let file = self.file.strip_prefix(dir).unwrap_or(&self.file);
+        let block_size = match self.block_size {
+            Some(bsize) => bsize.to_string(),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"file\": {}, \"kind\": {}, \"block_size\": {}, \"readers\": {}, \
+             \"writers\": {}, \"duration\": {}}}",
+            quote(&file.to_string_lossy()),
+            quote(self.kind),
+            block_size,
+            self.readers,
+            self.writers,
+            self.duration
+        )
+    }
+}
+
+lazy_static! {
+    static ref ARTIFACTS: Mutex<Vec<Artifact>> = Mutex::new(vec![]);
+}
+
+pub fn record(artifact: Artifact) {
+    let mut artifacts = ARTIFACTS.lock().unwrap();
+    if !artifacts.iter().any(|a| a.file == artifact.file) {
+        artifacts.push(artifact);
+    }
+}
+
+// write `manifest.json` into `dir`, listing recorded artifacts that made
+// it to disk, failed plots are left out.
+pub fn write(dir: &str, started: &str, environ: &Environ) -> io::Result<path::PathBuf> {
+    let dir = path::Path::new(dir);
+    let artifacts = ARTIFACTS.lock().unwrap();
+    let entries: Vec<String> = artifacts
+        .iter()
+        .filter(|a| a.file.exists())
+        .map(|a| format!("    {}", a.to_json_line(dir)))
+        .collect();
+    let text = format!(
+        "{{\n  \"version\": {},\n  \"started\": {},\n  \"env\": {},\n  \"artifacts\": [\n{}\n  ]\n}}\n",
+        MANIFEST_VERSION,
+        quote(started),
+        environ.to_json_line(),
+        entries.join(",\n")
+    );
+    let file = dir.join("manifest.json");
+    fs::write(&file, text)?;
+    Ok(file)
+}
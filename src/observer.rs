@@ -0,0 +1,21 @@
+use std::time;
+
+// Hook for custom per-operation instrumentation, like emitting tracing
+// spans or pushing metrics. Writers call it around every write and sync,
+// at the same points diskio times them. `id` is the writer's id and
+// `fpos` the file offset of the block. Methods run on the io path,
+// observers must be cheap, and are shared by all writers of a phase.
+pub trait OpObserver: Send + Sync {
+    fn before_write(&self, _id: isize, _fpos: u64, _len: usize) {}
+
+    fn after_write(&self, _id: isize, _fpos: u64, _len: usize, _elapsed: time::Duration) {}
+
+    fn before_sync(&self, _id: isize, _fpos: u64) {}
+
+    fn after_sync(&self, _id: isize, _fpos: u64, _elapsed: time::Duration) {}
+}
+
+// Default observer, does nothing.
+pub struct NoopObserver;
+
+impl OpObserver for NoopObserver {}
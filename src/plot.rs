@@ -1,7 +1,100 @@
+use std::fs;
 use std::path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    RwLock,
+};
 use std::time;
 
-use plotters::prelude::*;
+use plotters::{coord::Shift, prelude::*};
+
+use crate::stats;
+
+lazy_static! {
+    static ref SUBTITLE: RwLock<Option<String>> = RwLock::new(None);
+}
+
+// stamp `subtitle` below the title of every plot drawn from now on.
+pub fn set_subtitle(subtitle: String) {
+    *SUBTITLE.write().unwrap() = Some(subtitle);
+}
+
+// colors used across all plots.
+struct Theme {
+    background: RGBColor,
+    foreground: RGBColor, // text, axis and legend border
+    series: [RGBColor; 3],
+    bands: [RGBColor; 2], // p90..p99, p50..p90
+}
+
+static LIGHT: Theme = Theme {
+    background: RGBColor(255, 255, 255),
+    foreground: RGBColor(0, 0, 0),
+    series: [
+        RGBColor(255, 0, 0),
+        RGBColor(0, 0, 255),
+        RGBColor(0, 150, 0),
+    ],
+    bands: [RGBColor(255, 200, 200), RGBColor(255, 130, 130)],
+};
+
+static DARK: Theme = Theme {
+    background: RGBColor(30, 30, 30),
+    foreground: RGBColor(220, 220, 220),
+    series: [
+        RGBColor(255, 90, 90),
+        RGBColor(90, 160, 255),
+        RGBColor(90, 200, 90),
+    ],
+    bands: [RGBColor(90, 40, 40), RGBColor(160, 60, 60)],
+};
+
+static DARK_THEME: AtomicBool = AtomicBool::new(false);
+
+// select plot theme by name, `light` or `dark`.
+pub fn set_theme(name: &str) {
+    DARK_THEME.store(name == "dark", Ordering::Relaxed);
+}
+
+fn theme() -> &'static Theme {
+    match DARK_THEME.load(Ordering::Relaxed) {
+        true => &DARK,
+        false => &LIGHT,
+    }
+}
+
+// color of the i-th overlaid series, beyond the theme's own series
+// colors fall back to plotters' palette.
+fn series_color(i: usize) -> RGBColor {
+    match theme().series.get(i) {
+        Some(color) => RGBColor(color.0, color.1, color.2),
+        None => {
+            let (r, g, b) = SimpleColor::rgb(&Palette99::pick(i));
+            RGBColor(r, g, b)
+        }
+    }
+}
+
+fn font(size: u32) -> TextStyle<'static> {
+    ("Arial", size).into_font().color(&theme().foreground)
+}
+
+// fill background, draw title and subtitle, return the area left for chart.
+fn chart_area<'a>(
+    path: &'a path::Path,
+    title: &str,
+) -> Result<DrawingArea<BitMapBackend<'a>, Shift>, Box<dyn std::error::Error>> {
+    // bitmap backend panics when it fails to save on drop, find out
+    // early whether the file is writable.
+    fs::File::create(path)?;
+    let root = BitMapBackend::new(path, (1024, 768)).into_drawing_area();
+    root.fill(&theme().background)?;
+    let root = root.titled(title, font(30))?;
+    match SUBTITLE.read().unwrap().as_ref() {
+        Some(subtitle) => Ok(root.titled(subtitle, font(15))?),
+        None => Ok(root),
+    }
+}
 
 pub fn latency(
     path: path::PathBuf,
@@ -10,8 +103,7 @@ pub fn latency(
 ) -> Result<(), Box<dyn std::error::Error>> {
     println!("plotting latency graph {}", title);
 
-    let root = BitMapBackend::new(&path, (1024, 768)).into_drawing_area();
-    root.fill(&White)?;
+    let root = chart_area(&path, &title)?;
 
     let (xmin, xmax) = (0_u64, values.len() as u64);
     let (ymin, ymax) = (0_u64, values.iter().max().cloned().unwrap_or(0));
@@ -19,74 +111,727 @@ pub fn latency(
         .x_label_area_size(40)
         .y_label_area_size(60)
         .margin(10)
-        .caption(&title, ("Arial", 30).into_font())
         .build_ranged(xmin..xmax, ymin..ymax)?;
     scatter_ctx
         .configure_mesh()
+        .axis_style(&theme().foreground)
         .disable_x_mesh()
         .disable_y_mesh()
-        .label_style(("Arial", 15).into_font())
+        .label_style(font(15))
         .x_desc("N")
         .y_desc("Millisecond")
-        .axis_desc_style(("Arial", 20).into_font())
+        .axis_desc_style(font(20))
         .draw()?;
     scatter_ctx.draw_series(
-        values
-            .iter()
-            .enumerate()
-            .map(|(i, l)| Circle::new((i as u64, *l), 2, Red.filled())),
+        downsample(&values)
+            .into_iter()
+            .map(|(i, l)| Circle::new((i, l), 2, theme().series[0].filled())),
     )?;
 
-    values.sort();
-    let off = (values.len() as f64 * 0.99) as usize;
-    let p99 = time::Duration::from_nanos(values[off] * 1000);
-    println!("99th percentile latency: {:?}", p99);
+    if !values.is_empty() {
+        values.sort();
+        let off = (values.len() as f64 * 0.99) as usize;
+        let p99 = time::Duration::from_nanos(values[off] * 1000);
+        println!("99th percentile latency: {:?}", p99);
+    }
     Ok(())
 }
 
+// unit of throughput values, bytes or operations per interval.
+#[derive(Clone, Copy)]
+pub enum Unit {
+    Bytes,
+    Iops,
+}
+
+impl Unit {
+    // per-interval value to per-second rate.
+    fn rate(self, value: u64, secs: f64) -> f64 {
+        match self {
+            Unit::Bytes => (value as f64) / (1024_f64 * 1024_f64) / secs,
+            Unit::Iops => (value as f64) / secs,
+        }
+    }
+
+    fn desc(self) -> &'static str {
+        match self {
+            Unit::Bytes => "Throughput MB/sec",
+            Unit::Iops => "Throughput IOPS",
+        }
+    }
+}
+
 pub fn throughput(
     path: path::PathBuf,
     title: String,
+    interval: time::Duration,
+    unit: Unit,
     mut values: Vec<u64>,
+    cliff: Option<usize>, // interval where throughput fell off
 ) -> Result<(), Box<dyn std::error::Error>> {
     println!("plotting throughput graph {}", title);
 
+    let secs = interval.as_secs_f64();
     values.insert(0, 0);
     let throughputs: Vec<f64> = values
         .clone()
         .into_iter()
-        .map(|x| (x as f64) / (1024_f64 * 1024_f64))
+        .map(|x| unit.rate(x, secs))
         .collect();
 
-    let root = BitMapBackend::new(&path, (1024, 768)).into_drawing_area();
-    root.fill(&White)?;
+    let root = chart_area(&path, &title)?;
 
-    let (xmin, xmax) = (0_u64, values.len() as u64);
-    let (ymin, ymax) = (0_f64, values.iter().max().cloned().unwrap_or(0) as f64);
-    let ymax = ymax / (1024_f64 * 1024_f64);
-    let ymax = ymax + (ymax / 3_f64);
+    let (xmin, xmax) = (0_f64, (values.len() as f64) * secs);
+    let ymax = unit.rate(values.iter().max().cloned().unwrap_or(0), secs);
+    let (ymin, ymax) = (0_f64, ymax + (ymax / 3_f64));
     let mut cc = ChartBuilder::on(&root)
         .x_label_area_size(40)
         .y_label_area_size(60)
         .margin(10)
-        .caption(&title, ("Arial", 30).into_font())
         .build_ranged(xmin..xmax, ymin..ymax)?;
 
     cc.configure_mesh()
-        .line_style_2(&White)
-        .label_style(("Arial", 15).into_font())
+        .axis_style(&theme().foreground)
+        .line_style_2(&theme().background)
+        .label_style(font(15))
         .x_desc("Seconds")
-        .y_desc("Throughput MB/sec")
-        .axis_desc_style(("Arial", 20).into_font())
+        .y_desc(unit.desc())
+        .axis_desc_style(font(20))
         .draw()?;
 
     cc.draw_series(LineSeries::new(
         throughputs
             .into_iter()
             .enumerate()
-            .map(|(i, value)| (i as u64, value)),
-        &Red,
+            .map(|(i, value)| ((i as f64) * secs, value)),
+        &theme().series[0],
     ))?;
 
+    if let Some(at) = cliff {
+        let x = (at as f64) * secs;
+        cc.draw_series(LineSeries::new(
+            vec![(x, ymin), (x, ymax)],
+            &theme().series[1],
+        ))?;
+        let label = format!(" cliff at {:.0}s", x);
+        cc.draw_series(std::iter::once(Text::new(
+            label,
+            (x, ymax * 0.95),
+            font(15),
+        )))?;
+    }
+
+    Ok(())
+}
+
+// maximum number of points drawn in a scatter plot.
+const MAX_SCATTER_POINTS: usize = 50_000;
+
+// Drawing millions of samples is slow and ends up as a blob. Samples
+// beyond 99th percentile, the interesting tail, get half the budget and
+// the rest gets the other half, each strided to fit, the maximum is
+// always kept, so the total stays within MAX_SCATTER_POINTS.
+// Return a list of (sample-index, latency).
+fn downsample(values: &[u64]) -> Vec<(u64, u64)> {
+    if values.len() <= MAX_SCATTER_POINTS {
+        return values
+            .iter()
+            .enumerate()
+            .map(|(i, l)| (i as u64, *l))
+            .collect();
+    }
+
+    let p99 = {
+        let mut sorted = values.to_vec();
+        sorted.sort();
+        stats::percentile(&sorted, 0.99)
+    };
+    let imax = values
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, l)| **l)
+        .map(|(i, _)| i)
+        .unwrap();
+    let ntail = values.iter().filter(|l| **l > p99).count();
+    let nbulk = values.len() - ntail;
+    // one point of the tail's budget is held back for the maximum.
+    let tail_budget = MAX_SCATTER_POINTS / 2 - 1;
+    let bulk_budget = MAX_SCATTER_POINTS - MAX_SCATTER_POINTS / 2;
+    let tail_step = ntail.div_ceil(tail_budget).max(1);
+    let bulk_step = nbulk.div_ceil(bulk_budget).max(1);
+
+    let (mut tail_seen, mut bulk_seen) = (0, 0);
+    let mut points = vec![];
+    for (i, l) in values.iter().enumerate() {
+        let keep = if *l > p99 {
+            tail_seen += 1;
+            (tail_seen - 1) % tail_step == 0
+        } else {
+            bulk_seen += 1;
+            (bulk_seen - 1) % bulk_step == 0
+        };
+        if keep || i == imax {
+            points.push((i as u64, *l));
+        }
+    }
+    points
+}
+
+// number of latency buckets in histogram, the last bucket collects
+// everything beyond 99th percentile.
+const HISTOGRAM_BUCKETS: u64 = 50;
+
+pub fn latency_histogram(
+    path: path::PathBuf,
+    title: String,
+    mut values: Vec<u64>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("plotting latency histogram {}", title);
+
+    values.sort();
+    let p99 = stats::percentile(&values, 0.99);
+    let width = (p99 / HISTOGRAM_BUCKETS).max(1);
+    let mut counts = vec![0_u64; (HISTOGRAM_BUCKETS + 1) as usize];
+    for value in values.iter() {
+        counts[(value / width).min(HISTOGRAM_BUCKETS) as usize] += 1;
+    }
+
+    let root = chart_area(&path, &title)?;
+
+    let xmax = width * (HISTOGRAM_BUCKETS + 1);
+    let ymax = counts.iter().max().cloned().unwrap_or(0);
+    let ymax = ymax + (ymax / 10) + 1;
+    let mut cc = ChartBuilder::on(&root)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .margin(10)
+        .build_ranged(0_u64..xmax, 0_u64..ymax)?;
+
+    cc.configure_mesh()
+        .axis_style(&theme().foreground)
+        .line_style_2(&theme().background)
+        .label_style(font(15))
+        .x_desc("Microsecond")
+        .y_desc("Count")
+        .axis_desc_style(font(20))
+        .draw()?;
+
+    cc.draw_series(counts.iter().enumerate().map(|(i, count)| {
+        let x = (i as u64) * width;
+        Rectangle::new([(x, 0), (x + width, *count)], theme().series[0].filled())
+    }))?;
+
+    let p50 = time::Duration::from_micros(stats::percentile(&values, 0.50));
+    let p99 = time::Duration::from_micros(p99);
+    println!("50th percentile latency: {:?}, 99th: {:?}", p50, p99);
+    Ok(())
+}
+
+pub fn latency_compare(
+    path: path::PathBuf,
+    title: String,
+    series: Vec<(String, Vec<u64>)>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("plotting latency comparison graph {}", title);
+
+    let root = chart_area(&path, &title)?;
+
+    let xmax = series.iter().map(|(_, vs)| vs.len()).max().unwrap_or(0);
+    let ymax = series
+        .iter()
+        .filter_map(|(_, vs)| vs.iter().max().cloned())
+        .max()
+        .unwrap_or(0);
+    let mut scatter_ctx = ChartBuilder::on(&root)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .margin(10)
+        .build_ranged(0_u64..(xmax as u64), 0_u64..ymax)?;
+    scatter_ctx
+        .configure_mesh()
+        .axis_style(&theme().foreground)
+        .disable_x_mesh()
+        .disable_y_mesh()
+        .label_style(font(15))
+        .x_desc("N")
+        .y_desc("Millisecond")
+        .axis_desc_style(font(20))
+        .draw()?;
+
+    for (i, (label, values)) in series.iter().enumerate() {
+        let color = series_color(i);
+        scatter_ctx
+            .draw_series(
+                downsample(values)
+                    .into_iter()
+                    .map(|(i, l)| Circle::new((i, l), 2, color.filled())),
+            )?
+            .label(label.as_str())
+            .legend(move |(x, y)| Circle::new((x + 10, y), 4, color.filled()));
+    }
+    scatter_ctx
+        .configure_series_labels()
+        .background_style(&theme().background)
+        .border_style(&theme().foreground)
+        .label_font(font(12))
+        .draw()?;
+
+    for (label, mut values) in series.into_iter() {
+        if values.is_empty() {
+            continue;
+        }
+        values.sort();
+        let off = (values.len() as f64 * 0.99) as usize;
+        let p99 = time::Duration::from_nanos(values[off] * 1000);
+        println!("99th percentile latency for {}: {:?}", label, p99);
+    }
+    Ok(())
+}
+
+pub fn throughput_compare(
+    path: path::PathBuf,
+    title: String,
+    interval: time::Duration,
+    unit: Unit,
+    series: Vec<(String, Vec<u64>)>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("plotting throughput comparison graph {}", title);
+
+    let secs = interval.as_secs_f64();
+    let root = chart_area(&path, &title)?;
+
+    let xmax = series.iter().map(|(_, vs)| vs.len() + 1).max().unwrap_or(0);
+    let ymax = series
+        .iter()
+        .filter_map(|(_, vs)| vs.iter().max().cloned())
+        .max()
+        .unwrap_or(0);
+    let ymax = unit.rate(ymax, secs);
+    let ymax = ymax + (ymax / 3_f64);
+    let mut cc = ChartBuilder::on(&root)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .margin(10)
+        .build_ranged(0_f64..((xmax as f64) * secs), 0_f64..ymax)?;
+
+    cc.configure_mesh()
+        .axis_style(&theme().foreground)
+        .line_style_2(&theme().background)
+        .label_style(font(15))
+        .x_desc("Seconds")
+        .y_desc(unit.desc())
+        .axis_desc_style(font(20))
+        .draw()?;
+
+    for (i, (label, values)) in series.iter().enumerate() {
+        let color = series_color(i);
+        let throughputs = std::iter::once(0)
+            .chain(values.iter().cloned())
+            .map(|x| unit.rate(x, secs));
+        cc.draw_series(LineSeries::new(
+            throughputs
+                .enumerate()
+                .map(|(i, value)| ((i as f64) * secs, value)),
+            &color,
+        ))?
+        .label(label.as_str())
+        .legend(move |(x, y)| Path::new(vec![(x, y), (x + 20, y)], &color));
+    }
+    cc.configure_series_labels()
+        .background_style(&theme().background)
+        .border_style(&theme().foreground)
+        .label_font(font(12))
+        .draw()?;
+
     Ok(())
 }
+
+// number of time windows used to compute rolling percentiles.
+const BAND_WINDOWS: u64 = 100;
+
+pub fn latency_bands(
+    path: path::PathBuf,
+    title: String,
+    times: &[u64],
+    values: &[u64],
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("plotting latency bands graph {}", title);
+
+    let tmin = times.iter().min().cloned().unwrap_or(0);
+    let tmax = times.iter().max().cloned().unwrap_or(0);
+    let width = ((tmax - tmin) / BAND_WINDOWS).max(1);
+
+    // bucket samples by time window, and compute percentiles per window.
+    let mut windows: Vec<Vec<u64>> = vec![vec![]; (((tmax - tmin) / width) + 1) as usize];
+    for (t, l) in times.iter().zip(values.iter()) {
+        windows[((t - tmin) / width) as usize].push(*l);
+    }
+    let bands: Vec<(f64, f64, u64, u64, u64)> = windows
+        .into_iter()
+        .enumerate()
+        .filter(|(_, ls)| !ls.is_empty())
+        .map(|(i, mut ls)| {
+            ls.sort();
+            let x1 = ((i as u64) * width) as f64 / 1_000_000_f64;
+            let x2 = (((i + 1) as u64) * width) as f64 / 1_000_000_f64;
+            let p50 = stats::percentile(&ls, 0.50);
+            let p90 = stats::percentile(&ls, 0.90);
+            let p99 = stats::percentile(&ls, 0.99);
+            (x1, x2, p50, p90, p99)
+        })
+        .collect();
+
+    let root = chart_area(&path, &title)?;
+
+    let xmax = (((tmax - tmin) / width + 1) * width) as f64 / 1_000_000_f64;
+    let ymax = bands.iter().map(|b| b.4).max().unwrap_or(0);
+    let ymax = ymax + (ymax / 10) + 1;
+    let mut cc = ChartBuilder::on(&root)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .margin(10)
+        .build_ranged(0_f64..xmax, 0_u64..ymax)?;
+
+    cc.configure_mesh()
+        .axis_style(&theme().foreground)
+        .line_style_2(&theme().background)
+        .label_style(font(15))
+        .x_desc("Seconds")
+        .y_desc("Microsecond")
+        .axis_desc_style(font(20))
+        .draw()?;
+
+    let (p99_color, p90_color) = (&theme().bands[0], &theme().bands[1]);
+    let p50_color = &theme().series[0];
+    cc.draw_series(bands.iter().map(|(x1, x2, _, p90, p99)| {
+        Rectangle::new([(*x1, *p90), (*x2, *p99)], p99_color.filled())
+    }))?
+    .label("p90..p99")
+    .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 20, y + 5)], p99_color.filled()));
+    cc.draw_series(bands.iter().map(|(x1, x2, p50, p90, _)| {
+        Rectangle::new([(*x1, *p50), (*x2, *p90)], p90_color.filled())
+    }))?
+    .label("p50..p90")
+    .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 20, y + 5)], p90_color.filled()));
+    cc.draw_series(LineSeries::new(
+        bands
+            .iter()
+            .map(|(x1, x2, p50, _, _)| ((x1 + x2) / 2_f64, *p50)),
+        p50_color,
+    ))?
+    .label("p50")
+    .legend(move |(x, y)| Path::new(vec![(x, y), (x + 20, y)], p50_color));
+    cc.configure_series_labels()
+        .background_style(&theme().background)
+        .border_style(&theme().foreground)
+        .label_font(font(12))
+        .draw()?;
+
+    Ok(())
+}
+
+// number of time columns and latency rows in the heatmap, the last row
+// collects everything beyond 99th percentile.
+const HEATMAP_COLUMNS: u64 = 100;
+const HEATMAP_ROWS: u64 = 50;
+
+pub fn latency_heatmap(
+    path: path::PathBuf,
+    title: String,
+    times: &[u64],
+    values: &[u64],
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("plotting latency heatmap {}", title);
+
+    let tmin = times.iter().min().cloned().unwrap_or(0);
+    let tmax = times.iter().max().cloned().unwrap_or(0);
+    let width = ((tmax - tmin) / HEATMAP_COLUMNS).max(1);
+    let ncols = ((tmax - tmin) / width) + 1;
+    let p99 = {
+        let mut sorted = values.to_vec();
+        sorted.sort();
+        stats::percentile(&sorted, 0.99)
+    };
+    let height = (p99 / HEATMAP_ROWS).max(1);
+
+    // count samples falling into each (time, latency) cell.
+    let mut cells = vec![0_u64; (ncols * (HEATMAP_ROWS + 1)) as usize];
+    for (t, l) in times.iter().zip(values.iter()) {
+        let col = (t - tmin) / width;
+        let row = (l / height).min(HEATMAP_ROWS);
+        cells[(col * (HEATMAP_ROWS + 1) + row) as usize] += 1;
+    }
+    // density spans orders of magnitude, shade on log scale.
+    let max_count = cells.iter().max().cloned().unwrap_or(0);
+    let (bg, fg) = (&theme().background, &theme().series[0]);
+    let shade = |count: u64| {
+        let v = ((count as f64).ln_1p() / (max_count as f64).ln_1p()).min(1_f64);
+        let mix = |b: u8, f: u8| ((b as f64) + ((f as f64) - (b as f64)) * v) as u8;
+        RGBColor(mix(bg.0, fg.0), mix(bg.1, fg.1), mix(bg.2, fg.2))
+    };
+
+    let root = chart_area(&path, &title)?;
+
+    let xmax = (ncols * width) as f64 / 1_000_000_f64;
+    let ymax = height * (HEATMAP_ROWS + 1);
+    let mut cc = ChartBuilder::on(&root)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .margin(10)
+        .build_ranged(0_f64..xmax, 0_u64..ymax)?;
+
+    cc.configure_mesh()
+        .axis_style(&theme().foreground)
+        .disable_x_mesh()
+        .disable_y_mesh()
+        .label_style(font(15))
+        .x_desc("Seconds")
+        .y_desc("Microsecond")
+        .axis_desc_style(font(20))
+        .draw()?;
+
+    cc.draw_series(
+        cells
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(i, count)| {
+                let (col, row) = (i as u64 / (HEATMAP_ROWS + 1), i as u64 % (HEATMAP_ROWS + 1));
+                let x1 = (col * width) as f64 / 1_000_000_f64;
+                let x2 = ((col + 1) * width) as f64 / 1_000_000_f64;
+                let (y1, y2) = (row * height, (row + 1) * height);
+                Rectangle::new([(x1, y1), (x2, y2)], shade(*count).filled())
+            }),
+    )?;
+
+    Ok(())
+}
+
+// Grid of one value per (row, column) cell, shaded from background to the
+// first series color by magnitude, cells without a value are left blank.
+pub fn sweep_matrix(
+    path: path::PathBuf,
+    title: String,
+    rows: &[String],
+    cols: &[String],
+    cells: &[Vec<Option<f64>>],
+    desc: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("plotting sweep matrix {}", title);
+
+    let values = cells.iter().flatten().filter_map(|v| *v);
+    let (vmin, vmax) = values.fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    let (bg, fg) = (&theme().background, &theme().series[0]);
+    let shade = |value: f64| {
+        let v = match vmax > vmin {
+            true => (value - vmin) / (vmax - vmin),
+            false => 1_f64,
+        };
+        let v = 0.15 + 0.85 * v; // keep the smallest value visible.
+        let mix = |b: u8, f: u8| ((b as f64) + ((f as f64) - (b as f64)) * v) as u8;
+        RGBColor(mix(bg.0, fg.0), mix(bg.1, fg.1), mix(bg.2, fg.2))
+    };
+
+    let root = chart_area(&path, &title)?;
+    let root = root.titled(desc, font(20))?;
+    let (width, height) = root.dim_in_pixel();
+    let (left, top) = (160_i32, 40_i32);
+    let cw = (width as i32 - left - 20) / (cols.len().max(1) as i32);
+    let ch = (height as i32 - top - 20) / (rows.len().max(1) as i32);
+
+    for (j, col) in cols.iter().enumerate() {
+        let x = left + (j as i32) * cw + 10;
+        root.draw(&Text::new(col.clone(), (x, 10), font(15)))?;
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let y = top + (i as i32) * ch + ch / 2 - 8;
+        root.draw(&Text::new(row.clone(), (10, y), font(15)))?;
+        for (j, cell) in cells[i].iter().enumerate() {
+            let (x, y) = (left + (j as i32) * cw, top + (i as i32) * ch);
+            let rect = [(x, y), (x + cw - 2, y + ch - 2)];
+            if let Some(value) = cell {
+                root.draw(&Rectangle::new(rect, shade(*value).filled()))?;
+                let label = format!("{:.1}", value);
+                root.draw(&Text::new(label, (x + 10, y + ch / 2 - 8), font(15)))?;
+            }
+            root.draw(&Rectangle::new(rect, &theme().foreground))?;
+        }
+    }
+    Ok(())
+}
+
+// latency against file offset of each operation, reveals positional
+// effects like HDD zones or SSD cache regions.
+pub fn latency_vs_offset(
+    path: path::PathBuf,
+    title: String,
+    offsets: &[u64],
+    values: &[u64],
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("plotting latency vs offset {}", title);
+
+    let root = chart_area(&path, &title)?;
+
+    let mb = |offset: u64| (offset as f64) / (1024_f64 * 1024_f64);
+    let xmax = mb(offsets.iter().max().cloned().unwrap_or(0)).max(1_f64);
+    let ymax = values.iter().max().cloned().unwrap_or(0);
+    let mut cc = ChartBuilder::on(&root)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .margin(10)
+        .build_ranged(0_f64..xmax, 0_u64..ymax)?;
+    cc.configure_mesh()
+        .axis_style(&theme().foreground)
+        .disable_x_mesh()
+        .disable_y_mesh()
+        .label_style(font(15))
+        .x_desc("Offset MB")
+        .y_desc("Microsecond")
+        .axis_desc_style(font(20))
+        .draw()?;
+    cc.draw_series(downsample(values).into_iter().map(|(i, l)| {
+        let offset = mb(offsets[i as usize]);
+        Circle::new((offset, l), 2, theme().series[0].filled())
+    }))?;
+    Ok(())
+}
+
+// number of points plotted along each percentile's convergence line.
+const CONVERGENCE_POINTS: usize = 200;
+
+// running p50 and p99 estimates as samples accumulate in time order, a
+// line that flattens early means the run collected enough samples to
+// trust its tail.
+pub fn percentile_convergence(
+    path: path::PathBuf,
+    title: String,
+    times: &[u64],
+    values: &[u64],
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("plotting percentile convergence graph {}", title);
+
+    let mut samples: Vec<(u64, u64)> = times.iter().cloned().zip(values.iter().cloned()).collect();
+    samples.sort_by_key(|(t, _)| *t);
+
+    let quantiles = [("p50", 0.50), ("p99", 0.99)];
+    let mut estimators: Vec<stats::Quantile> = quantiles
+        .iter()
+        .map(|(_, q)| stats::Quantile::new(*q))
+        .collect();
+    let every = (samples.len() / CONVERGENCE_POINTS).max(1);
+    let mut lines: Vec<Vec<(u64, u64)>> = vec![vec![]; quantiles.len()];
+    for (i, (_, latency)) in samples.iter().enumerate() {
+        estimators.iter_mut().for_each(|e| e.add(*latency));
+        if (i + 1) % every == 0 || i + 1 == samples.len() {
+            for (line, e) in lines.iter_mut().zip(estimators.iter()) {
+                line.push(((i + 1) as u64, e.estimate()));
+            }
+        }
+    }
+
+    let root = chart_area(&path, &title)?;
+
+    let xmax = samples.len() as u64;
+    let ymax = lines
+        .iter()
+        .flat_map(|line| line.iter().map(|(_, y)| *y))
+        .max()
+        .unwrap_or(0);
+    let ymax = ymax + (ymax / 10) + 1;
+    let mut cc = ChartBuilder::on(&root)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .margin(10)
+        .build_ranged(0_u64..xmax.max(1), 0_u64..ymax)?;
+    cc.configure_mesh()
+        .axis_style(&theme().foreground)
+        .line_style_2(&theme().background)
+        .label_style(font(15))
+        .x_desc("Samples")
+        .y_desc("Microsecond")
+        .axis_desc_style(font(20))
+        .draw()?;
+
+    for (i, ((label, _), line)) in quantiles.iter().zip(lines).enumerate() {
+        let color = series_color(i);
+        cc.draw_series(LineSeries::new(line.into_iter(), &color))?
+            .label(*label)
+            .legend(move |(x, y)| Path::new(vec![(x, y), (x + 20, y)], &series_color(i)));
+    }
+    cc.configure_series_labels()
+        .background_style(&theme().background)
+        .border_style(&theme().foreground)
+        .label_font(font(12))
+        .draw()?;
+
+    Ok(())
+}
+
+// throughput against thread count, a line per workload, its knee is
+// where adding threads stops paying off.
+pub fn throughput_scaling(
+    path: path::PathBuf,
+    title: String,
+    unit: Unit,
+    series: Vec<(String, Vec<(u64, f64)>)>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("plotting thread scaling graph {}", title);
+
+    let root = chart_area(&path, &title)?;
+
+    let points = || series.iter().flat_map(|(_, ps)| ps.iter());
+    let xmax = points().map(|(n, _)| *n).max().unwrap_or(1);
+    let ymax = points().map(|(_, rate)| *rate).fold(0_f64, f64::max);
+    let ymax = ymax + (ymax / 3_f64);
+    let mut cc = ChartBuilder::on(&root)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .margin(10)
+        .build_ranged(0_u64..(xmax + 1), 0_f64..ymax.max(1_f64))?;
+
+    cc.configure_mesh()
+        .axis_style(&theme().foreground)
+        .line_style_2(&theme().background)
+        .label_style(font(15))
+        .x_desc("Threads")
+        .y_desc(unit.desc())
+        .axis_desc_style(font(20))
+        .draw()?;
+
+    for (i, (label, points)) in series.iter().enumerate() {
+        let color = series_color(i);
+        cc.draw_series(LineSeries::new(points.iter().cloned(), &color))?
+            .label(label.as_str())
+            .legend(move |(x, y)| Path::new(vec![(x, y), (x + 20, y)], &color));
+        cc.draw_series(
+            points
+                .iter()
+                .map(|p| Circle::new(*p, 3, series_color(i).filled())),
+        )?;
+    }
+    cc.configure_series_labels()
+        .background_style(&theme().background)
+        .border_style(&theme().foreground)
+        .label_font(font(12))
+        .draw()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downsample_cap() {
+        // a heavy tail, more than the whole budget beyond 99th percentile.
+        let mut values: Vec<u64> = (0..1_000_000).map(|i| i % 100).collect();
+        for (i, l) in values.iter_mut().enumerate().step_by(10) {
+            *l = 1000 + (i as u64);
+        }
+        values[123_457] = 10_000_000;
+        let points = downsample(&values);
+        assert!(points.len() <= MAX_SCATTER_POINTS, "{}", points.len());
+        assert!(points.contains(&(123_457, 10_000_000)));
+    }
+}
@@ -0,0 +1,266 @@
+use std::{
+    fs, io,
+    sync::{mpsc, Arc, Mutex},
+    thread, time,
+};
+
+use crate::{block::Block, error::DiskioError, humanize, log_rw_mix, stats, stats::Stats};
+
+// io threads issuing replayed operations, an operation due while all of
+// them are busy waits in queue, and its latency includes the wait.
+const REPLAY_THREADS: usize = 16;
+const REPLAY_FILL: u8 = 0xAB;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Read,
+    Write,
+    Sync,
+}
+
+// One traced operation, at `at` microseconds into the trace.
+#[derive(Clone, Copy, Debug)]
+struct Record {
+    at: u64,
+    offset: u64,
+    size: usize,
+    op: Op,
+}
+
+// Parse a trace, one `timestamp,offset,size,op` record per line, the
+// timestamp in microseconds and op one of `R`, `W` or `F` for a flush,
+// like blkparse's RWBS field. Blank lines, `#` comments and a header
+// line are skipped. Records are returned in time order, relative to the
+// first one.
+fn parse(text: &str) -> io::Result<Vec<Record>> {
+    let invalid = |lineno: usize, msg: &str| {
+        let msg = format!("trace line {}: {}", lineno + 1, msg);
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    };
+    let mut records = vec![];
+    for (lineno, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() != 4 {
+            return Err(invalid(lineno, "expected `timestamp,offset,size,op`"));
+        }
+        let at = match fields[0].parse::<u64>() {
+            Ok(at) => at,
+            Err(_) if records.is_empty() => continue, // header
+            Err(err) => return Err(invalid(lineno, &err.to_string())),
+        };
+        let offset = fields[1]
+            .parse::<u64>()
+            .map_err(|err| invalid(lineno, &err.to_string()))?;
+        let size = fields[2]
+            .parse::<usize>()
+            .map_err(|err| invalid(lineno, &err.to_string()))?;
+        let op = match fields[3] {
+            "R" | "r" | "read" => Op::Read,
+            "W" | "w" | "write" => Op::Write,
+            "F" | "f" | "flush" => Op::Sync,
+            op => return Err(invalid(lineno, &format!("unknown op `{}`", op))),
+        };
+        records.push(Record {
+            at,
+            offset,
+            size,
+            op,
+        });
+    }
+    records.sort_by_key(|r| r.at);
+    let first = records.first().map(|r| r.at).unwrap_or(0);
+    records.iter_mut().for_each(|r| r.at -= first);
+    Ok(records)
+}
+
+// `diskio replay <trace> <file>`, issue every traced operation on `file`
+// at its recorded time relative to the start, open-loop, latency is
+// measured from when the operation was due.
+pub fn run(trace: &str, file: &str) -> Result<(), DiskioError> {
+    let records = parse(&fs::read_to_string(trace)?)?;
+    let span = records.last().map(|r| r.at).unwrap_or(0);
+    let end = records
+        .iter()
+        .filter(|r| r.op != Op::Sync)
+        .map(|r| r.offset + (r.size as u64))
+        .max()
+        .unwrap_or(0);
+    let max_size = records.iter().map(|r| r.size).max().unwrap_or(0);
+    let fd = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(file)?;
+    let file_size = fd.metadata()?.len();
+    if file_size < end {
+        println!(
+            "warning: trace reaches offset {}, beyond `{}` of {}, reads past its end are short",
+            humanize(end as usize),
+            file,
+            humanize(file_size as usize)
+        );
+    }
+    println!(
+        "replaying {} operations over {:?} from `{}` on `{}` ..",
+        records.len(),
+        time::Duration::from_micros(span),
+        trace,
+        file
+    );
+
+    let fd = Arc::new(fd);
+    let (tx, rx) = mpsc::channel::<(time::SystemTime, Record)>();
+    let rx = Arc::new(Mutex::new(rx));
+    let threads: Vec<thread::JoinHandle<Result<Stats, DiskioError>>> = (0..REPLAY_THREADS)
+        .map(|_| {
+            let (fd, rx) = (Arc::clone(&fd), Arc::clone(&rx));
+            thread::spawn(move || replay_thread(&fd, &rx, max_size))
+        })
+        .collect();
+
+    // dispatch on schedule, however far behind the io threads are.
+    let (start, epoch) = (time::Instant::now(), time::SystemTime::now());
+    let mut late = time::Duration::default();
+    for record in records.into_iter() {
+        let due = time::Duration::from_micros(record.at);
+        match due.checked_sub(start.elapsed()) {
+            Some(wait) => thread::sleep(wait),
+            None => late = late.max(start.elapsed() - due),
+        }
+        // every io thread is gone, their error is returned below.
+        if tx.send((epoch + due, record)).is_err() {
+            break;
+        }
+    }
+    drop(tx);
+
+    let (mut ss, mut failed) = (Stats::new(time::Duration::from_secs(1)), None);
+    for thread in threads.into_iter() {
+        match thread.join().unwrap() {
+            Ok(ts) => ss.join(ts),
+            Err(err) => failed = failed.or(Some(err)),
+        }
+    }
+    if let Some(err) = failed {
+        return Err(err);
+    }
+    let elapsed = start.elapsed();
+    let mut latencies = ss.sync_latencies.clone();
+    latencies.sort();
+    let p = |q| time::Duration::from_micros(stats::percentile(&latencies, q));
+    println!(
+        "replayed {} operations, {} in {:?}, trace spans {:?}, dispatch late by up to {:?}",
+        ss.count,
+        humanize(ss.bytes as usize),
+        elapsed,
+        time::Duration::from_micros(span),
+        late
+    );
+    println!(
+        "latency p50 {:?} p99 {:?} p999 {:?} max {:?}",
+        p(0.50),
+        p(0.99),
+        p(0.999),
+        p(1.0)
+    );
+    log_rw_mix(&ss);
+    if ss.syncs > 0 {
+        println!("flushes {}", ss.syncs);
+    }
+    Ok(())
+}
+
+fn replay_thread(
+    fd: &fs::File,
+    rx: &Mutex<mpsc::Receiver<(time::SystemTime, Record)>>,
+    max_size: usize,
+) -> Result<Stats, DiskioError> {
+    use std::os::unix::fs::FileExt;
+
+    let mut ss = Stats::new(time::Duration::from_secs(1));
+    let mut block = Block::new(max_size, REPLAY_FILL);
+    loop {
+        let (due, record) = match rx.lock().unwrap().recv() {
+            Ok(item) => item,
+            Err(_) => break Ok(ss),
+        };
+        let buf = &mut block[..record.size];
+        let n = match record.op {
+            Op::Read => fd.read_at(buf, record.offset)?,
+            Op::Write => {
+                fd.write_all_at(buf, record.offset)?;
+                record.size
+            }
+            Op::Sync => {
+                fd.sync_data()?;
+                ss.syncs += 1;
+                0
+            }
+        };
+        if let Some(latency) = ss.click(due, n as u64)? {
+            match record.op {
+                Op::Read => ss.read_latencies.push(latency),
+                Op::Write => ss.write_latencies.push(latency),
+                Op::Sync => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "timestamp,offset,size,op\n\
+                    # a comment\n\
+                    1000,0,4096,W\n\
+                    \n\
+                    1500, 4096, 512, r\n\
+                    2000,0,0,F\n";
+        let records = parse(text).unwrap();
+        let ops: Vec<(u64, u64, usize, Op)> = records
+            .iter()
+            .map(|r| (r.at, r.offset, r.size, r.op))
+            .collect();
+        let want = vec![
+            (0, 0, 4096, Op::Write),
+            (500, 4096, 512, Op::Read),
+            (1000, 0, 0, Op::Sync),
+        ];
+        assert_eq!(ops, want);
+    }
+
+    #[test]
+    fn test_parse_out_of_order() {
+        let records = parse("300,8192,4096,W\n100,0,4096,R\n200,4096,4096,W\n").unwrap();
+        let at: Vec<u64> = records.iter().map(|r| r.at).collect();
+        assert_eq!(at, vec![0, 100, 200]);
+        let offsets: Vec<u64> = records.iter().map(|r| r.offset).collect();
+        assert_eq!(offsets, vec![0, 4096, 8192]);
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let malformed = [
+            "100,0,4096",
+            "100,0,4096,W,extra",
+            "100,0,4096,X",
+            "100,-1,4096,W",
+            "100,0,4K,W",
+            "100,0,4096,W\nabc,0,4096,W",
+        ];
+        for text in malformed.iter() {
+            let err = parse(text).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", text);
+        }
+        let err = parse("100,0,4096,W\n200,0,4096,Q").unwrap_err();
+        assert!(err.to_string().starts_with("trace line 2:"), "{}", err);
+    }
+}
@@ -0,0 +1,81 @@
+use std::{fs, io, io::Write};
+
+// Version of the results row layout, bumped whenever a field changes
+// meaning or is removed, new fields can be added without a bump.
+pub const SCHEMA_VERSION: u32 = 1;
+
+// One row of the results database, a JSON object on a single line.
+pub struct Row {
+    fields: Vec<String>,
+}
+
+impl Row {
+    pub fn new() -> Row {
+        let row = Row { fields: vec![] };
+        row.num("schema", SCHEMA_VERSION)
+    }
+
+    pub fn text(mut self, key: &str, value: &str) -> Row {
+        self.fields
+            .push(format!("{}: {}", quote(key), quote(value)));
+        self
+    }
+
+    pub fn num<T: ToString>(mut self, key: &str, value: T) -> Row {
+        self.fields
+            .push(format!("{}: {}", quote(key), value.to_string()));
+        self
+    }
+
+    // floats that are absent, or not finite, become null.
+    pub fn float(mut self, key: &str, value: Option<f64>) -> Row {
+        let value = match value {
+            Some(v) if v.is_finite() => format!("{:.3}", v),
+            _ => "null".to_string(),
+        };
+        self.fields.push(format!("{}: {}", quote(key), value));
+        self
+    }
+
+    // `value` must already be valid JSON.
+    pub fn raw(mut self, key: &str, value: String) -> Row {
+        self.fields.push(format!("{}: {}", quote(key), value));
+        self
+    }
+
+    pub fn to_json_line(&self) -> String {
+        format!("{{{}}}", self.fields.join(", "))
+    }
+}
+
+// append rows to a newline delimited JSON file, created if missing.
+pub fn append(file: &str, rows: &[Row]) -> io::Result<()> {
+    let mut fd = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)?;
+    let mut text = String::new();
+    for row in rows.iter() {
+        text.push_str(&row.to_json_line());
+        text.push('\n');
+    }
+    fd.write_all(text.as_bytes())?;
+    fd.sync_data()
+}
+
+pub fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
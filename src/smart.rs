@@ -0,0 +1,89 @@
+use std::process;
+
+use crate::error::DiskioError;
+
+// bytes per LBA reported by ATA Total_LBAs_Written attribute.
+const ATA_LBA_SIZE: u64 = 512;
+// NVMe reports "Data Units Written" in thousands of 512 byte units.
+const NVME_DATA_UNIT: u64 = 512 * 1000;
+
+// Total bytes written to the device, as reported by SMART attributes,
+// using `smartctl -A <device>`. Requires smartmontools, and usually root.
+pub fn bytes_written(device: &str) -> Result<u64, DiskioError> {
+    let output = process::Command::new("smartctl")
+        .arg("-A")
+        .arg(device)
+        .output()
+        .map_err(|err| DiskioError::Smart(format!("smartctl: {}", err)))?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    parse(&text).ok_or_else(|| {
+        let msg = format!("smartctl {}: total bytes written not reported", device);
+        DiskioError::Smart(msg)
+    })
+}
+
+// total bytes written, from the output of `smartctl -A`.
+fn parse(text: &str) -> Option<u64> {
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if line.contains("Total_LBAs_Written") {
+            // ID# ATTRIBUTE_NAME FLAG VALUE WORST THRESH TYPE UPDATED WHEN_FAILED RAW_VALUE
+            if let Some(Ok(lbas)) = fields.get(9).map(|f| f.parse::<u64>()) {
+                return Some(lbas * ATA_LBA_SIZE);
+            }
+        } else if let Some(value) = line.strip_prefix("Data Units Written:") {
+            // Data Units Written:   12,345,678 [6.32 TB]
+            let units: String = value
+                .trim()
+                .chars()
+                .take_while(|ch| ch.is_ascii_digit() || *ch == ',')
+                .filter(|ch| *ch != ',')
+                .collect();
+            if let Ok(units) = units.parse::<u64>() {
+                return Some(units * NVME_DATA_UNIT);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ata() {
+        let text = "\
+=== START OF READ SMART DATA SECTION ===
+SMART Attributes Data Structure revision number: 1
+Vendor Specific SMART Attributes with Thresholds:
+ID# ATTRIBUTE_NAME          FLAG     VALUE WORST THRESH TYPE      UPDATED  WHEN_FAILED RAW_VALUE
+  9 Power_On_Hours          0x0032   099   099   000    Old_age   Always       -       1234
+241 Total_LBAs_Written      0x0032   099   099   000    Old_age   Always       -       2000000
+";
+        assert_eq!(parse(text), Some(2_000_000 * 512));
+    }
+
+    #[test]
+    fn test_parse_nvme() {
+        let text = "\
+=== START OF SMART DATA SECTION ===
+SMART/Health Information (NVMe Log 0x02)
+Critical Warning:                   0x00
+Data Units Read:                    9,876,543 [5.05 TB]
+Data Units Written:                 12,345,678 [6.32 TB]
+Host Read Commands:                 123,456,789
+";
+        assert_eq!(parse(text), Some(12_345_678 * 512_000));
+    }
+
+    #[test]
+    fn test_parse_unreported() {
+        let text = "\
+ID# ATTRIBUTE_NAME          FLAG     VALUE WORST THRESH TYPE      UPDATED  WHEN_FAILED RAW_VALUE
+  9 Power_On_Hours          0x0032   099   099   000    Old_age   Always       -       1234
+";
+        assert_eq!(parse(text), None);
+        assert_eq!(parse(""), None);
+    }
+}
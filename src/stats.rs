@@ -1,47 +1,555 @@
 use std::convert::TryInto;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Mutex,
+};
 use std::time;
 
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::cpustat::CpuUsage;
 use crate::error;
 
+// cap on latencies buffered between two drain_recent() calls.
+const MAX_RECENT: usize = 1_000_000;
+
+static WATCH: AtomicBool = AtomicBool::new(false);
+
+// cap on latencies kept per thread, zero for unbounded.
+static MAX_SAMPLES: AtomicUsize = AtomicUsize::new(0);
+
+// latency is recorded for 1 in every SAMPLE_RATE operations.
+static SAMPLE_RATE: AtomicU64 = AtomicU64::new(1);
+
+// Latency log record, little endian u64 start time, microseconds since
+// unix epoch, followed by little endian u64 latency in microseconds.
+const LOG_RECORD_SIZE: usize = 16;
+
+// latency log records buffered per thread before they are written out.
+const LOG_BUFFER: usize = 4096 * LOG_RECORD_SIZE;
+
+static LATENCY_LOGGING: AtomicBool = AtomicBool::new(false);
+
+// with --latency-log every latency goes to the log, a thread keeps only
+// a reservoir of this many, unless --max-samples asks otherwise.
+const LOGGED_MAX_SAMPLES: usize = 100_000;
+
+lazy_static! {
+    static ref RECENT: Mutex<Vec<u64>> = Mutex::new(vec![]);
+    static ref LATENCY_LOG: Mutex<Option<io::BufWriter<fs::File>>> = Mutex::new(None);
+}
+
+// share latencies from all threads, so they can be watched while running.
+pub fn watch_latencies() {
+    WATCH.store(true, Ordering::Relaxed);
+}
+
+// beyond `n` operations, keep a uniform random sample of `n` latencies
+// per thread, percentiles computed from them become estimates.
+pub fn max_samples(n: usize) {
+    MAX_SAMPLES.store(n, Ordering::Relaxed);
+}
+
+// time only 1 in `n` operations, all of them still count for throughput,
+// so that timing overhead doesn't bias ultra-fast devices.
+pub fn sample_rate(n: u64) {
+    SAMPLE_RATE.store(n.max(1), Ordering::Relaxed);
+}
+
+// latencies recorded since the last call.
+pub fn drain_recent() -> Vec<u64> {
+    RECENT.lock().unwrap().drain(..).collect()
+}
+
+// stream every latency sample from all threads into `path`.
+pub fn open_latency_log(path: &str) -> io::Result<()> {
+    let fd = fs::File::create(path)?;
+    *LATENCY_LOG.lock().unwrap() = Some(io::BufWriter::new(fd));
+    LATENCY_LOGGING.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn close_latency_log() -> io::Result<()> {
+    LATENCY_LOGGING.store(false, Ordering::Relaxed);
+    match LATENCY_LOG.lock().unwrap().take() {
+        Some(mut log) => log.flush(),
+        None => Ok(()),
+    }
+}
+
+// write out, and clear, records buffered by a thread.
+fn write_latency_log(records: &mut Vec<u8>) -> io::Result<()> {
+    if let Some(log) = LATENCY_LOG.lock().unwrap().as_mut() {
+        log.write_all(records)?;
+    }
+    records.clear();
+    Ok(())
+}
+
+// read back a latency log as a list of (start-time, latency).
+pub fn read_latency_log(path: &str) -> io::Result<Vec<(u64, u64)>> {
+    let mut data = vec![];
+    fs::File::open(path)?.read_to_end(&mut data)?;
+    let records = data.chunks_exact(LOG_RECORD_SIZE).map(|record| {
+        let at = u64::from_le_bytes(record[..8].try_into().unwrap());
+        let latency = u64::from_le_bytes(record[8..].try_into().unwrap());
+        (at, latency)
+    });
+    Ok(records.collect())
+}
+
+// time spent in each syscall of an operation, summed over sampled ops.
+#[derive(Clone, Default)]
+pub struct Profile {
+    pub ops: u64,
+    pub seek: time::Duration,
+    pub write: time::Duration,
+    pub sync: time::Duration,
+}
+
+impl Profile {
+    pub fn add(&mut self, seek: time::Duration, write: time::Duration, sync: time::Duration) {
+        self.ops += 1;
+        self.seek += seek;
+        self.write += write;
+        self.sync += sync;
+    }
+
+    fn join(&mut self, other: &Profile) {
+        self.ops += other.ops;
+        self.seek += other.seek;
+        self.write += other.write;
+        self.sync += other.sync;
+    }
+}
+
+#[derive(Clone)]
 pub struct Stats {
     tp_second: time::SystemTime,
     tp_current: u64,
+    tp_ops: u64,
+    rng: SmallRng,
+    slot: Option<usize>, // where the last latency was sampled
+    log: Vec<u8>,        // latency log records yet to be written
+    pub count: u64,      // operations clicked, sampled or not
+    pub interval: time::Duration,
     pub file_size: u64,
+    pub syncs: u64,
+    pub retries: u64,
+    pub bytes: u64,
+    pub thread_bytes: Vec<u64>,
     pub sync_latencies: Vec<u64>,
+    pub read_latencies: Vec<u64>,  // only for --rw-mix
+    pub write_latencies: Vec<u64>, // only for --rw-mix
+    pub op_sizes: Vec<u64>,        // only for --file-size-dist
+    pub offsets: Vec<u64>,         // only when plotting random access
+    pub latency_times: Vec<u64>,
     pub throughputs: Vec<u64>,
+    pub ops: Vec<u64>, // operations per interval
+    pub profile: Profile,
+    pub prepare: time::Duration, // allocating and filling io buffers
+    pub refill: time::Duration,  // per-operation buffer handling
+    pub threads: Vec<Stats>,     // kept with --per-thread-stats
+    pub cpu: Option<CpuUsage>,   // averaged over the phase
 }
 
 impl Stats {
-    pub fn new() -> Stats {
+    pub fn new(interval: time::Duration) -> Stats {
         Stats {
             tp_second: time::SystemTime::now(),
             tp_current: 0,
+            tp_ops: 0,
+            rng: SmallRng::seed_from_u64(0),
+            slot: None,
+            log: vec![],
+            count: 0,
+            interval,
             sync_latencies: vec![],
+            read_latencies: vec![],
+            write_latencies: vec![],
+            op_sizes: vec![],
+            offsets: vec![],
+            latency_times: vec![],
             throughputs: vec![],
+            ops: vec![],
+            profile: Default::default(),
+            prepare: Default::default(),
+            refill: Default::default(),
             file_size: Default::default(),
+            syncs: Default::default(),
+            retries: Default::default(),
+            bytes: Default::default(),
+            thread_bytes: vec![],
+            threads: vec![],
+            cpu: None,
         }
     }
 
-    pub fn click(&mut self, start: time::SystemTime, size: u64) -> Result<(), error::DiskioError> {
-        if self.tp_second.elapsed()?.as_secs() == 1 {
+    // seed the rng used for reservoir sampling, from --seed and the
+    // thread id, so that threads keep different samples and a run is
+    // reproducible. Drawn apart from offsets, like failure injection.
+    pub fn seed(&mut self, seed: u128) {
+        let seed = seed ^ 0x6469_736b_696f_7361_6d70_6c65; // "diskiosample"
+                                                           // mixed by seed_from_u64, thread seeds differ only in a few bits.
+        self.rng = SmallRng::seed_from_u64((seed as u64) ^ ((seed >> 64) as u64));
+    }
+
+    // account an operation started at `start`, return its latency if it
+    // was sampled.
+    pub fn click(
+        &mut self,
+        start: time::SystemTime,
+        size: u64,
+    ) -> Result<Option<u64>, error::DiskioError> {
+        if self.tp_second.elapsed()? >= self.interval {
             self.throughputs.push(self.tp_current);
+            self.ops.push(self.tp_ops);
             self.tp_second = time::SystemTime::now();
             self.tp_current = 0;
+            self.tp_ops = 0;
         } else {
-            self.tp_current += size;
+            self.tp_current = self.tp_current.saturating_add(size);
+            self.tp_ops = self.tp_ops.saturating_add(1);
+        }
+        self.bytes = self.bytes.saturating_add(size);
+        self.count = self.count.saturating_add(1);
+        let rate = SAMPLE_RATE.load(Ordering::Relaxed);
+        if !self.count.is_multiple_of(rate) {
+            self.slot = None;
+            return Ok(None);
+        }
+        let latency = micros("latency", start.elapsed()?)?;
+        if WATCH.load(Ordering::Relaxed) {
+            let mut recent = RECENT.lock().unwrap();
+            if recent.len() < MAX_RECENT {
+                recent.push(latency);
+            }
         }
-        self.sync_latencies
-            .push(start.elapsed()?.as_micros().try_into().unwrap());
-        Ok(())
+        let at = micros("start time", start.duration_since(time::UNIX_EPOCH)?)?;
+        self.sample(at, latency, self.count / rate);
+        if LATENCY_LOGGING.load(Ordering::Relaxed) {
+            self.log.extend_from_slice(&at.to_le_bytes());
+            self.log.extend_from_slice(&latency.to_le_bytes());
+            if self.log.len() >= LOG_BUFFER {
+                write_latency_log(&mut self.log)?;
+            }
+        }
+        Ok(Some(latency))
+    }
+
+    // reservoir sampling, once --max-samples latencies are kept the n-th
+    // candidate operation replaces a random one with probability max/n.
+    fn sample(&mut self, at: u64, latency: u64, n: u64) {
+        let max = match MAX_SAMPLES.load(Ordering::Relaxed) {
+            0 if LATENCY_LOGGING.load(Ordering::Relaxed) => LOGGED_MAX_SAMPLES,
+            max => max,
+        };
+        self.slot = match self.sync_latencies.len() {
+            n if max == 0 || n < max => {
+                self.sync_latencies.push(latency);
+                self.latency_times.push(at);
+                Some(n)
+            }
+            _ => match self.rng.gen_range(0, n) as usize {
+                slot if slot < max => {
+                    self.sync_latencies[slot] = latency;
+                    self.latency_times[slot] = at;
+                    Some(slot)
+                }
+                _ => None,
+            },
+        };
     }
 
-    pub fn join(&mut self, other: Stats) {
+    // size of the last operation, kept along with its latency sample.
+    pub fn sample_size(&mut self, size: u64) {
+        tag(&mut self.op_sizes, self.slot, size)
+    }
+
+    // file offset of the last operation, kept along with its latency.
+    pub fn sample_offset(&mut self, offset: u64) {
+        tag(&mut self.offsets, self.slot, offset)
+    }
+
+    pub fn join(&mut self, mut other: Stats) {
+        // records still buffered by the thread go out as it is joined.
+        if let Err(err) = write_latency_log(&mut other.log) {
+            println!("warning: unable to write latency log: {}", err);
+        }
+        self.interval = other.interval;
         self.sync_latencies.extend_from_slice(&other.sync_latencies);
-        self.throughputs.resize(other.throughputs.len(), 0);
+        self.latency_times.extend_from_slice(&other.latency_times);
+        self.read_latencies.extend_from_slice(&other.read_latencies);
+        self.write_latencies
+            .extend_from_slice(&other.write_latencies);
+        self.op_sizes.extend_from_slice(&other.op_sizes);
+        self.offsets.extend_from_slice(&other.offsets);
+        let n = self.throughputs.len().max(other.throughputs.len());
+        self.throughputs.resize(n, 0);
         self.throughputs
             .iter_mut()
             .zip(other.throughputs.iter())
-            .for_each(|(x, y)| *x += *y);
-        self.file_size += other.file_size;
+            .for_each(|(x, y)| *x = x.saturating_add(*y));
+        self.ops.resize(self.ops.len().max(other.ops.len()), 0);
+        self.ops
+            .iter_mut()
+            .zip(other.ops.iter())
+            .for_each(|(x, y)| *x = x.saturating_add(*y));
+        self.profile.join(&other.profile);
+        self.prepare += other.prepare;
+        self.refill += other.refill;
+        self.file_size = self.file_size.saturating_add(other.file_size);
+        self.syncs = self.syncs.saturating_add(other.syncs);
+        self.count = self.count.saturating_add(other.count);
+        self.retries = self.retries.saturating_add(other.retries);
+        self.bytes = self.bytes.saturating_add(other.bytes);
+        self.thread_bytes.push(other.bytes);
+        // chains of an --async-files thread come along.
+        self.threads.extend(other.threads);
+    }
+}
+
+// `d` in microseconds, an error instead of a panic if it doesn't fit.
+fn micros(what: &'static str, d: time::Duration) -> Result<u64, error::DiskioError> {
+    d.as_micros()
+        .try_into()
+        .map_err(|_| error::DiskioError::Overflow(what))
+}
+
+fn tag(values: &mut Vec<u64>, slot: Option<usize>, value: u64) {
+    match slot {
+        Some(slot) if slot < values.len() => values[slot] = value,
+        Some(_) => values.push(value),
+        None => (),
+    }
+}
+
+// `sorted` must be in ascending order, `q` is a fraction between 0 and 1.
+pub fn percentile(sorted: &[u64], q: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let off = ((sorted.len() as f64) * q) as usize;
+    sorted[off.min(sorted.len() - 1)]
+}
+
+// a throughput cliff is a drop to at most CLIFF_RATIO of the rate before
+// it, with at least CLIFF_INTERVALS intervals on either side.
+const CLIFF_RATIO: f64 = 0.7;
+const CLIFF_INTERVALS: usize = 5;
+
+// Change point of a per-interval throughput series, the index of the
+// first interval after a sustained step down, like an SSD running out of
+// its SLC cache. The split with the least squared error of a two-level
+// fit is the candidate, accepted when the drop is deep enough and nine
+// in ten intervals after it stay below the midway level.
+pub fn cliff(values: &[u64]) -> Option<usize> {
+    let n = values.len();
+    if n < (CLIFF_INTERVALS * 2) {
+        return None;
+    }
+    let (mut sums, mut sq) = (vec![0_f64], 0_f64);
+    for value in values.iter() {
+        sums.push(sums.last().unwrap() + (*value as f64));
+        sq += (*value as f64).powi(2);
+    }
+    let total = sums[n];
+    let sse = |k: usize| {
+        let (before, after) = (sums[k], total - sums[k]);
+        sq - before.powi(2) / (k as f64) - after.powi(2) / ((n - k) as f64)
+    };
+    let at = (CLIFF_INTERVALS..=(n - CLIFF_INTERVALS))
+        .min_by(|a, b| sse(*a).partial_cmp(&sse(*b)).unwrap())?;
+    let before = sums[at] / (at as f64);
+    let after = (total - sums[at]) / ((n - at) as f64);
+    let midway = (before + after) / 2_f64;
+    let below = values[at..]
+        .iter()
+        .filter(|v| (**v as f64) < midway)
+        .count();
+    match after <= before * CLIFF_RATIO && below * 10 >= (n - at) * 9 {
+        true => Some(at),
+        false => None,
+    }
+}
+
+// Streaming estimate of quantile `q`, using the P-square algorithm of
+// Jain and Chlamtac, in constant memory. Five markers track the minimum,
+// q/2, q, (1+q)/2 and maximum, their heights are adjusted piecewise
+// parabolically as samples arrive.
+pub struct Quantile {
+    q: f64,
+    count: usize,
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl Quantile {
+    pub fn new(q: f64) -> Quantile {
+        Quantile {
+            q,
+            count: 0,
+            heights: [0_f64; 5],
+            positions: [1_f64, 2_f64, 3_f64, 4_f64, 5_f64],
+            desired: [
+                1_f64,
+                1_f64 + 2_f64 * q,
+                1_f64 + 4_f64 * q,
+                3_f64 + 2_f64 * q,
+                5_f64,
+            ],
+            increments: [0_f64, q / 2_f64, q, (1_f64 + q) / 2_f64, 1_f64],
+        }
+    }
+
+    pub fn add(&mut self, value: u64) {
+        let x = value as f64;
+        if self.count < 5 {
+            self.heights[self.count] = x;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            }
+            return;
+        }
+        self.count += 1;
+
+        let h = &mut self.heights;
+        let k = if x < h[0] {
+            h[0] = x;
+            0
+        } else if x >= h[4] {
+            h[4] = x;
+            3
+        } else {
+            (0..4).find(|&i| x < h[i + 1]).unwrap()
+        };
+        self.positions[k + 1..].iter_mut().for_each(|p| *p += 1_f64);
+        for (d, inc) in self.desired.iter_mut().zip(self.increments.iter()) {
+            *d += inc;
+        }
+
+        for i in 1..4 {
+            let d = self.desired[i] - self.positions[i];
+            let (np, nn) = (self.positions[i + 1], self.positions[i - 1]);
+            let n = self.positions[i];
+            if (d >= 1_f64 && np - n > 1_f64) || (d <= -1_f64 && nn - n < -1_f64) {
+                let d = d.signum();
+                let h = &mut self.heights;
+                let parabolic = h[i]
+                    + d / (np - nn)
+                        * ((n - nn + d) * (h[i + 1] - h[i]) / (np - n)
+                            + (np - n - d) * (h[i] - h[i - 1]) / (n - nn));
+                h[i] = if h[i - 1] < parabolic && parabolic < h[i + 1] {
+                    parabolic
+                } else {
+                    let j = if d > 0_f64 { i + 1 } else { i - 1 };
+                    h[i] + d * (h[j] - h[i]) / (self.positions[j] - n)
+                };
+                self.positions[i] += d;
+            }
+        }
+    }
+
+    pub fn estimate(&self) -> u64 {
+        match self.count {
+            0 => 0,
+            n if n < 5 => {
+                let mut values = self.heights[..n].to_vec();
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let off = ((n as f64) * self.q) as usize;
+                values[off.min(n - 1)] as u64
+            }
+            _ => self.heights[2] as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_micros_beyond_u32() {
+        let d = time::Duration::from_secs(5000);
+        assert_eq!(micros("x", d).unwrap(), 5_000_000_000);
+    }
+
+    #[test]
+    fn test_click_long_operation() {
+        let mut ss = Stats::new(time::Duration::from_secs(1));
+        let start = time::SystemTime::now() - time::Duration::from_secs(5000);
+        let latency = ss.click(start, 4096).unwrap().unwrap();
+        assert!(latency >= 5_000_000_000, "latency {}", latency);
+        assert!(latency < 5_000_000_000 + 60_000_000, "latency {}", latency);
+        assert_eq!(ss.sync_latencies, vec![latency]);
+    }
+
+    #[test]
+    fn test_latency_log_bounded() {
+        let path = std::env::temp_dir().join("diskio-test-latency.log");
+        open_latency_log(path.to_str().unwrap()).unwrap();
+        let mut ss = Stats::new(time::Duration::from_secs(1));
+        let n = LOGGED_MAX_SAMPLES + 1000;
+        for _ in 0..n {
+            ss.click(time::SystemTime::now(), 4096).unwrap();
+        }
+        let mut joined = Stats::new(time::Duration::from_secs(1));
+        joined.join(ss.clone());
+        close_latency_log().unwrap();
+
+        assert_eq!(ss.sync_latencies.len(), LOGGED_MAX_SAMPLES);
+        assert_eq!(ss.latency_times.len(), LOGGED_MAX_SAMPLES);
+        let records = read_latency_log(path.to_str().unwrap()).unwrap();
+        assert!(records.len() >= n, "{} records", records.len());
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_cliff() {
+        // SLC cache runs out at interval 30, rates jitter on either side.
+        let values: Vec<u64> = (0..60)
+            .map(|i| match i < 30 {
+                true => 2000 + (i % 3) * 50,
+                false => 600 + (i % 4) * 40,
+            })
+            .collect();
+        assert_eq!(cliff(&values), Some(30));
+
+        let flat: Vec<u64> = (0..60).map(|i| 1000 + (i % 5) * 20).collect();
+        assert_eq!(cliff(&flat), None);
+        // a shallow step is not a cliff.
+        let shallow: Vec<u64> = (0..60).map(|i| if i < 30 { 1000 } else { 800 }).collect();
+        assert_eq!(cliff(&shallow), None);
+        // too few intervals after the drop.
+        let late: Vec<u64> = (0..60).map(|i| if i < 57 { 1000 } else { 100 }).collect();
+        assert_eq!(cliff(&late), None);
+        assert_eq!(cliff(&values[..9]), None);
+    }
+
+    #[test]
+    fn test_quantile() {
+        // uniform over 0..100_000, in random order.
+        let mut rng = SmallRng::seed_from_u64(1);
+        let values: Vec<u64> = (0..100_000).map(|_| rng.gen_range(0, 100_000)).collect();
+        let mut sorted = values.clone();
+        sorted.sort_unstable();
+        for q in [0.5, 0.9, 0.99].iter() {
+            let mut quantile = Quantile::new(*q);
+            values.iter().for_each(|v| quantile.add(*v));
+            let (got, want) = (quantile.estimate(), percentile(&sorted, *q));
+            let err = ((got as f64) - (want as f64)).abs();
+            assert!(err < 1000_f64, "q {} got {} want {}", q, got, want);
+        }
+
+        // exact below five samples.
+        let mut quantile = Quantile::new(0.5);
+        assert_eq!(quantile.estimate(), 0);
+        [30, 10, 20].iter().for_each(|v| quantile.add(*v));
+        assert_eq!(quantile.estimate(), 20);
     }
 }
@@ -0,0 +1,62 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    thread, time,
+};
+
+// Pace operations to a fixed rate shared by all threads holding the same
+// Throttle. Each operation claims the next free slot, `1/rate` seconds
+// after the previous one, and sleeps until it is due. Slots missed while
+// threads lag behind are not made up with a burst, unless the throttle
+// is open-loop, where slots are a fixed schedule and missed slots pile
+// up as backlog.
+pub struct Throttle {
+    start: time::Instant,
+    epoch: time::SystemTime,
+    open_loop: bool,
+    gap: u64,        // nanoseconds between slots
+    next: AtomicU64, // next free slot, nanoseconds since start
+}
+
+impl Throttle {
+    pub fn new(rate: u64) -> Throttle {
+        Throttle {
+            start: time::Instant::now(),
+            epoch: time::SystemTime::now(),
+            open_loop: false,
+            gap: 1_000_000_000 / rate.max(1),
+            next: AtomicU64::new(0),
+        }
+    }
+
+    pub fn new_open_loop(rate: u64) -> Throttle {
+        let mut throttle = Throttle::new(rate);
+        throttle.open_loop = true;
+        throttle
+    }
+
+    // wait for the next slot, for open-loop throttle return the time the
+    // operation was scheduled to start, latency is measured from there.
+    pub fn wait(&self) -> Option<time::SystemTime> {
+        let now = self.start.elapsed().as_nanos() as u64;
+        let mut next = self.next.load(Ordering::Relaxed);
+        let slot = loop {
+            let slot = if self.open_loop { next } else { next.max(now) };
+            match self.next.compare_exchange_weak(
+                next,
+                slot + self.gap,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break slot,
+                Err(n) => next = n,
+            }
+        };
+        if slot > now {
+            thread::sleep(time::Duration::from_nanos(slot - now));
+        }
+        match self.open_loop {
+            true => Some(self.epoch + time::Duration::from_nanos(slot)),
+            false => None,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::io;
+
+use io_uring::{opcode, squeue, types, IoUring};
+
+// Keeps many write+fsync chains in flight from a single thread, a thin
+// wrapper over the io-uring crate.
+pub struct Ring {
+    ring: IoUring,
+}
+
+impl Ring {
+    pub fn new(entries: u32) -> io::Result<Ring> {
+        Ok(Ring {
+            ring: IoUring::new(entries)?,
+        })
+    }
+
+    // queue a write of `iov` at `off` to `fd`, linked to an fsync of
+    // `fd` that starts only once the write completes. Completions carry
+    // `user_data` and `user_data + 1`, a failed write completes its fsync
+    // with ECANCELED, so both always complete.
+    //
+    // Safety: the kernel reads `iov` and the buffer it points to after
+    // this returns, both must stay put until both completions are reaped.
+    pub unsafe fn write_fsync(
+        &mut self,
+        fd: i32,
+        iov: &libc::iovec,
+        off: u64,
+        user_data: u64,
+    ) -> io::Result<()> {
+        let write = opcode::Writev::new(types::Fd(fd), iov as *const libc::iovec, 1)
+            .offset(off)
+            .build()
+            .flags(squeue::Flags::IO_LINK)
+            .user_data(user_data);
+        let fsync = opcode::Fsync::new(types::Fd(fd))
+            .build()
+            .user_data(user_data + 1);
+        let entries = [write, fsync];
+        if self.ring.submission().push_multiple(&entries).is_ok() {
+            return Ok(());
+        }
+        // submission queue is full, hand it to the kernel and retry.
+        self.enter(0)?;
+        self.ring
+            .submission()
+            .push_multiple(&entries)
+            .map_err(|_| io::Error::other("io_uring submission queue full"))
+    }
+
+    // submit queued entries and wait for at least one completion, then
+    // return all available completions as (user_data, result).
+    pub fn submit_and_wait(&mut self) -> io::Result<Vec<(u64, i32)>> {
+        self.enter(1)?;
+        let completions = self
+            .ring
+            .completion()
+            .map(|cqe| (cqe.user_data(), cqe.result()))
+            .collect();
+        Ok(completions)
+    }
+
+    fn enter(&mut self, want: usize) -> io::Result<usize> {
+        loop {
+            match self.ring.submit_and_wait(want) {
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                res => break res,
+            }
+        }
+    }
+}
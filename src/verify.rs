@@ -0,0 +1,347 @@
+use std::convert::TryInto;
+use std::fs;
+use std::io::{self, Read};
+
+// Durability marker, stamped on every block written with
+// --durability-markers. Head of the block carries the magic, the
+// block's sequence number and the block size, tail of the block repeats
+// the sequence number, so that a torn block is told from a whole one.
+const MAGIC: u64 = 0x4449_534b_494f_4d4b;
+pub const MARKER_SIZE: usize = 32;
+
+// Data file header, written at offset 0 with --durability-markers and
+// followed by the marked blocks. Fields are in the writer's native byte
+// order, tagged by ENDIAN_TAG, so that the file can be verified on a
+// machine of either endianness.
+//
+//   0..8    HEADER_MAGIC
+//   8..12   ENDIAN_TAG
+//   12..16  version
+//   16..24  block size
+//   24..25  fill byte
+//   32..48  seed
+const HEADER_MAGIC: &[u8; 8] = b"DISKIO\0\0";
+const ENDIAN_TAG: u32 = 0x0102_0304;
+const VERSION: u32 = 1;
+pub const HEADER_SIZE: usize = 64;
+
+#[derive(Debug)]
+pub struct Header {
+    pub version: u32,
+    pub block_size: u64,
+    pub fill_byte: u8,
+    pub seed: u128,
+}
+
+impl Header {
+    pub fn new(block_size: usize, fill_byte: u8, seed: u128) -> Header {
+        Header {
+            version: VERSION,
+            block_size: block_size as u64,
+            fill_byte,
+            seed,
+        }
+    }
+
+    pub fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut buf = [0_u8; HEADER_SIZE];
+        buf[..8].copy_from_slice(HEADER_MAGIC);
+        buf[8..12].copy_from_slice(&ENDIAN_TAG.to_ne_bytes());
+        buf[12..16].copy_from_slice(&self.version.to_ne_bytes());
+        buf[16..24].copy_from_slice(&self.block_size.to_ne_bytes());
+        buf[24] = self.fill_byte;
+        buf[32..48].copy_from_slice(&self.seed.to_ne_bytes());
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Header, String> {
+        if buf.len() < HEADER_SIZE || &buf[..8] != HEADER_MAGIC {
+            return Err("no diskio header".to_string());
+        }
+        let tag: [u8; 4] = buf[8..12].try_into().unwrap();
+        let swap = match u32::from_ne_bytes(tag) {
+            ENDIAN_TAG => false,
+            tag if tag.swap_bytes() == ENDIAN_TAG => true,
+            tag => return Err(format!("bad endian tag {:#x}", tag)),
+        };
+        let u32_at = |off: usize| {
+            let n = u32::from_ne_bytes(buf[off..off + 4].try_into().unwrap());
+            if swap {
+                n.swap_bytes()
+            } else {
+                n
+            }
+        };
+        let u64_at = |off: usize| {
+            let n = u64::from_ne_bytes(buf[off..off + 8].try_into().unwrap());
+            if swap {
+                n.swap_bytes()
+            } else {
+                n
+            }
+        };
+        let seed = u128::from_ne_bytes(buf[32..48].try_into().unwrap());
+        let header = Header {
+            version: u32_at(12),
+            block_size: u64_at(16),
+            fill_byte: buf[24],
+            seed: if swap { seed.swap_bytes() } else { seed },
+        };
+        match header.version {
+            VERSION => Ok(header),
+            version => Err(format!("unsupported header version {}", version)),
+        }
+    }
+}
+
+pub fn mark(block: &mut [u8], seq: u64) {
+    let n = block.len();
+    block[..8].copy_from_slice(&MAGIC.to_le_bytes());
+    block[8..16].copy_from_slice(&seq.to_le_bytes());
+    block[16..24].copy_from_slice(&(n as u64).to_le_bytes());
+    block[n - 8..].copy_from_slice(&seq.to_le_bytes());
+}
+
+fn word(block: &[u8], off: usize) -> u64 {
+    u64::from_le_bytes(block[off..off + 8].try_into().unwrap())
+}
+
+// sequence number of a whole, marked block, whose payload still holds
+// the fill byte.
+fn sequence(block: &[u8], fill_byte: u8) -> Option<u64> {
+    let n = block.len();
+    let filled = block[24..n - 8].iter().all(|b| *b == fill_byte);
+    match word(block, 0) == MAGIC && word(block, 16) == (n as u64) && filled {
+        true if word(block, 8) == word(block, n - 8) => Some(word(block, 8)),
+        _ => None,
+    }
+}
+
+// read into `buf` until it is full or the file ends, return the number
+// of bytes read, short only at the end of file.
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(m) => n += m,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(n)
+}
+
+// What a scan of a data file found.
+#[derive(Debug)]
+struct Report {
+    header: Header,
+    blocks: usize,
+    marked: usize,
+    torn: usize,
+    beyond: usize, // marked blocks beyond the durable offset
+    max_seq: u64,
+    durable: usize,
+    size: usize,
+}
+
+// read back a file written with --durability-markers, after a crash or
+// power-cut, and report up to where it is durable. The file is streamed
+// a block at a time, it may well be larger than memory.
+pub fn run(file: &str) -> io::Result<()> {
+    let mut r = io::BufReader::new(fs::File::open(file)?);
+    let rp = match scan(&mut r)? {
+        Ok(rp) => rp,
+        Err(err) => {
+            println!("`{}` can't be verified: {}", file, err);
+            return Ok(());
+        }
+    };
+
+    println!(
+        "verified `{}`, header v{}, block-size {}, fill-byte {:#x}, seed {}",
+        file, rp.header.version, rp.header.block_size, rp.header.fill_byte, rp.header.seed
+    );
+    println!(
+        "  {} of {} blocks carry a whole marker",
+        rp.marked, rp.blocks
+    );
+    println!("  {} torn blocks", rp.torn);
+    println!("  highest sequence {}", rp.max_seq);
+    println!("  durable up to offset {} of {}", rp.durable, rp.size);
+    if rp.beyond > 0 {
+        println!("  {} marked blocks beyond the durable offset", rp.beyond);
+    }
+    Ok(())
+}
+
+// scan a data file, the inner error is for a file without a usable
+// header.
+fn scan(r: &mut impl Read) -> io::Result<Result<Report, String>> {
+    let mut head = [0_u8; HEADER_SIZE];
+    let n = read_full(r, &mut head)?;
+    let header = match Header::decode(&head[..n]) {
+        Ok(header) => header,
+        Err(err) => return Ok(Err(err)),
+    };
+    let block_size = header.block_size as usize;
+    if block_size < MARKER_SIZE {
+        return Ok(Err(format!("corrupt header, block-size {}", block_size)));
+    }
+
+    // durable prefix ends at the first block without a whole marker,
+    // marked blocks beyond it were persisted out of order.
+    let (mut durable, mut marked, mut torn, mut beyond, mut max_seq) = (None, 0, 0, 0, 0);
+    let (mut blocks, mut size) = (0, HEADER_SIZE);
+    let mut buf = vec![0_u8; block_size];
+    loop {
+        let n = read_full(r, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        let block = &buf[..n];
+        let seq = match n == block_size {
+            true => sequence(block, header.fill_byte),
+            false => None,
+        };
+        match seq {
+            Some(seq) => {
+                marked += 1;
+                max_seq = max_seq.max(seq);
+                beyond += if durable.is_some() { 1 } else { 0 };
+            }
+            None => {
+                durable = durable.or(Some(size));
+                if block.len() >= 8 && word(block, 0) == MAGIC {
+                    torn += 1;
+                }
+            }
+        }
+        blocks += 1;
+        size += n;
+    }
+    Ok(Ok(Report {
+        header,
+        blocks,
+        marked,
+        torn,
+        beyond,
+        max_seq,
+        durable: durable.unwrap_or(size),
+        size,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: usize = 256;
+    const FILL: u8 = 0xAB;
+
+    // header followed by `n` marked blocks.
+    fn data_file(n: u64) -> Vec<u8> {
+        let mut data = Header::new(BLOCK, FILL, 42).encode().to_vec();
+        for seq in 0..n {
+            let mut block = vec![FILL; BLOCK];
+            mark(&mut block, seq);
+            data.extend_from_slice(&block);
+        }
+        data
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let seed = 0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10;
+        let buf = Header::new(4096, FILL, seed).encode();
+        let header = Header::decode(&buf).unwrap();
+        assert_eq!(header.version, VERSION);
+        assert_eq!(header.block_size, 4096);
+        assert_eq!(header.fill_byte, FILL);
+        assert_eq!(header.seed, seed);
+    }
+
+    #[test]
+    fn test_header_other_endian() {
+        // header as written by a machine of the other byte order.
+        let seed: u128 = 42;
+        let mut buf = Header::new(4096, FILL, seed).encode();
+        buf[8..12].copy_from_slice(&ENDIAN_TAG.swap_bytes().to_ne_bytes());
+        buf[12..16].copy_from_slice(&VERSION.swap_bytes().to_ne_bytes());
+        buf[16..24].copy_from_slice(&4096_u64.swap_bytes().to_ne_bytes());
+        buf[32..48].copy_from_slice(&seed.swap_bytes().to_ne_bytes());
+        let header = Header::decode(&buf).unwrap();
+        assert_eq!((header.version, header.block_size), (VERSION, 4096));
+        assert_eq!(header.seed, seed);
+    }
+
+    #[test]
+    fn test_header_rejected() {
+        let buf = Header::new(4096, FILL, 0).encode();
+        assert!(Header::decode(&buf[..HEADER_SIZE - 1]).is_err());
+        assert!(Header::decode(&[0; HEADER_SIZE]).is_err());
+        let mut bad = buf;
+        bad[8..12].copy_from_slice(&0xdead_beef_u32.to_ne_bytes());
+        assert!(Header::decode(&bad).is_err());
+        let mut bad = buf;
+        bad[12..16].copy_from_slice(&(VERSION + 1).to_ne_bytes());
+        assert!(Header::decode(&bad).is_err());
+    }
+
+    #[test]
+    fn test_marker() {
+        let mut block = vec![FILL; BLOCK];
+        mark(&mut block, 7);
+        assert_eq!(sequence(&block, FILL), Some(7));
+        // torn, the tail never made it.
+        let mut torn = block.clone();
+        torn[BLOCK - 8..].copy_from_slice(&[0; 8]);
+        assert_eq!(sequence(&torn, FILL), None);
+        // payload doesn't hold the fill byte.
+        let mut garbled = block;
+        garbled[100] = !FILL;
+        assert_eq!(sequence(&garbled, FILL), None);
+    }
+
+    #[test]
+    fn test_scan_whole() {
+        let data = data_file(4);
+        let rp = scan(&mut data.as_slice()).unwrap().unwrap();
+        assert_eq!((rp.blocks, rp.marked, rp.torn, rp.beyond), (4, 4, 0, 0));
+        assert_eq!(rp.max_seq, 3);
+        assert_eq!((rp.durable, rp.size), (data.len(), data.len()));
+    }
+
+    #[test]
+    fn test_scan_torn_tail() {
+        let mut data = data_file(4);
+        // last block torn, its tail sequence zeroed.
+        let n = data.len();
+        data[n - 8..].copy_from_slice(&[0; 8]);
+        // and a partial block beyond it.
+        data.extend_from_slice(&[FILL; BLOCK / 2]);
+        let rp = scan(&mut data.as_slice()).unwrap().unwrap();
+        assert_eq!((rp.blocks, rp.marked, rp.torn, rp.beyond), (5, 3, 1, 0));
+        assert_eq!(rp.durable, HEADER_SIZE + 3 * BLOCK);
+        assert_eq!(rp.size, data.len());
+    }
+
+    #[test]
+    fn test_scan_out_of_order() {
+        let mut data = data_file(4);
+        // second block never persisted, the ones after it were.
+        let off = HEADER_SIZE + BLOCK;
+        data[off..off + BLOCK].copy_from_slice(&[0; BLOCK]);
+        let rp = scan(&mut data.as_slice()).unwrap().unwrap();
+        assert_eq!((rp.marked, rp.torn, rp.beyond), (3, 0, 2));
+        assert_eq!(rp.durable, off);
+    }
+
+    #[test]
+    fn test_scan_no_header() {
+        let data = vec![0; 1024];
+        assert!(scan(&mut data.as_slice()).unwrap().is_err());
+        let data = Header::new(8, FILL, 0).encode();
+        assert!(scan(&mut data.as_ref()).unwrap().is_err());
+    }
+}